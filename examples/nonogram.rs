@@ -0,0 +1,34 @@
+//! Solves a nonogram clue file and prints the grid.
+//!
+//! Usage: `cargo run --example nonogram <file>`, or reads standard input
+//! when no file is given.
+use advent_of_code::nonogram::Nonogram;
+use std::io::Read;
+use std::process;
+
+fn main() {
+    let input = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("Cannot read {path}: {e}");
+            process::exit(1);
+        }),
+        None => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .expect("Cannot read standard input");
+            input
+        }
+    };
+    let puzzle = Nonogram::parse(&input).unwrap_or_else(|e| {
+        eprintln!("Invalid clues: {e}");
+        process::exit(1);
+    });
+    match puzzle.solve() {
+        Some(grid) => print!("{grid}"),
+        None => {
+            eprintln!("No solution");
+            process::exit(1);
+        }
+    }
+}
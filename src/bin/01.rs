@@ -1,71 +1,128 @@
 advent_of_code::solution!(1);
 
-pub fn part_one(input: &str) -> Option<u32> {
-    input
-        .lines()
-        .map(|line| {
-            let digits = line
-                .chars()
-                .filter_map(|c| c.to_digit(10))
-                .collect::<Vec<u32>>();
-            digits.first().unwrap() * 10 + digits.last().unwrap()
-        })
-        .sum::<u32>()
-        .into()
+use std::collections::VecDeque;
+
+const DIGITS: &[(&str, u32)] = &[
+    ("0", 0),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const ENGLISH: &[(&str, u32)] = &[
+    ("zero", 0),
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+/// A match of a word of the vocabulary, on bytes `start..end` of the text.
+#[derive(Debug, PartialEq)]
+struct Match {
+    start: usize,
+    end: usize,
+    value: u32,
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    input
-        .lines()
-        .map(|line| {
-            // Clearly, not the most efficient way to do this, I could have used
-            // the leading character to determine which word to check, and split
-            // the slice after a match.  This was efficent to type.
-            let tok2u32 = [
-                ("0", 0u32),
-                ("1", 1),
-                ("2", 2),
-                ("3", 3),
-                ("4", 4),
-                ("5", 5),
-                ("6", 6),
-                ("7", 7),
-                ("8", 8),
-                ("9", 9),
-                ("zero", 0),
-                ("one", 1),
-                ("two", 2),
-                ("three", 3),
-                ("four", 4),
-                ("five", 5),
-                ("six", 6),
-                ("seven", 7),
-                ("eight", 8),
-                ("nine", 9),
-            ];
-            let mut first_digit = 0;
-            let mut first_index = line.len();
-            let mut last_digit = 0;
-            let mut last_index = 0;
-            // Assume that the first and last word never overlap! Gasp!
-            for (word, val) in tok2u32.iter() {
-                if let Some(index) = line.find(word) {
-                    if index < first_index {
-                        first_digit = *val;
-                        first_index = index;
-                    }
+/// Aho-Corasick automaton over the bytes of a vocabulary's words. It reads
+/// the text once and reports every match, overlapping ones included.
+struct Scanner {
+    /// Next state for each state and byte
+    next: Vec<[usize; 256]>,
+    /// Length and value of the words ending at each state
+    words: Vec<Vec<(usize, u32)>>,
+}
+
+impl Scanner {
+    fn new<'a>(vocabulary: impl IntoIterator<Item = &'a (&'a str, u32)>) -> Self {
+        // Trie of the words, with state 0 as the root
+        let mut next = vec![[0; 256]];
+        let mut words = vec![vec![]];
+        for (word, value) in vocabulary {
+            let mut state = 0;
+            for b in word.bytes() {
+                if next[state][b as usize] == 0 {
+                    next.push([0; 256]);
+                    words.push(vec![]);
+                    next[state][b as usize] = next.len() - 1;
                 }
-                if let Some(index) = line.rfind(word) {
-                    if last_index <= index {
-                        last_digit = *val;
-                        last_index = index;
-                    }
+                state = next[state][b as usize];
+            }
+            words[state].push((word.len(), *value));
+        }
+        // Breadth first, complete transitions missing from the trie with the
+        // ones of the longest proper suffix in the trie (the fail state),
+        // which is already complete.
+        let mut fail = vec![0; next.len()];
+        let mut open = (0..256)
+            .map(|b| next[0][b])
+            .filter(|s| *s != 0)
+            .collect::<VecDeque<_>>();
+        while let Some(state) = open.pop_front() {
+            let suffix_words = words[fail[state]].clone();
+            words[state].extend(suffix_words);
+            let fallback = next[fail[state]];
+            for (child, fallback) in next[state].iter_mut().zip(fallback) {
+                if *child == 0 {
+                    *child = fallback;
+                } else {
+                    fail[*child] = fallback;
+                    open.push_back(*child);
                 }
             }
-            first_digit * 10 + last_digit
-        })
-        .sum::<u32>()
-        .into()
+        }
+        Self { next, words }
+    }
+
+    /// All matches in `text`, in the order of their end.
+    fn matches<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Match> + 'a {
+        text.bytes()
+            .enumerate()
+            .scan(0, |state, (i, b)| {
+                *state = self.next[*state][b as usize];
+                Some((i + 1, &self.words[*state]))
+            })
+            .flat_map(|(end, words)| {
+                words.iter().map(move |(len, value)| Match {
+                    start: end - len,
+                    end,
+                    value: *value,
+                })
+            })
+    }
+
+    /// First and last value in `line` as a two-digit number, if any.
+    fn calibration(&self, line: &str) -> Option<u32> {
+        let matches = self.matches(line).collect::<Vec<_>>();
+        let first = matches.iter().min_by_key(|m| m.start)?;
+        let last = matches.iter().max_by_key(|m| m.start)?;
+        Some(first.value * 10 + last.value)
+    }
+}
+
+fn calibrations(input: &str, scanner: &Scanner) -> Option<u32> {
+    input.lines().map(|line| scanner.calibration(line)).sum()
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    calibrations(input, &Scanner::new(DIGITS))
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    // Words may overlap, e.g. "eightwo" is 8 then 2
+    calibrations(input, &Scanner::new(DIGITS.iter().chain(ENGLISH)))
 }
 
 #[cfg(test)]
@@ -83,4 +140,49 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(281));
     }
+
+    #[test]
+    fn test_matches() {
+        let scanner = Scanner::new(DIGITS.iter().chain(ENGLISH));
+        let matches = scanner.matches("xeightwone3").collect::<Vec<_>>();
+        let found = matches
+            .iter()
+            .map(|m| (m.start, m.end, m.value))
+            .collect::<Vec<_>>();
+        assert_eq!(found, [(1, 6, 8), (5, 8, 2), (7, 10, 1), (10, 11, 3)]);
+        assert_eq!(scanner.calibration("eightwo"), Some(82));
+        assert_eq!(scanner.calibration("twone"), Some(21));
+        assert_eq!(scanner.calibration("oneight"), Some(18));
+        assert_eq!(scanner.calibration("seven"), Some(77));
+        assert_eq!(scanner.calibration("nothing"), None);
+        // A word within another one
+        let nested = Scanner::new(&[("seventeen", 17), ("even", 2), ("teen", 13)]);
+        let values = nested
+            .matches("seventeen")
+            .map(|m| m.value)
+            .collect::<Vec<_>>();
+        assert_eq!(values, [2, 17, 13]);
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        const FRENCH: &[(&str, u32)] = &[
+            ("zéro", 0),
+            ("un", 1),
+            ("deux", 2),
+            ("trois", 3),
+            ("quatre", 4),
+            ("cinq", 5),
+            ("six", 6),
+            ("sept", 7),
+            ("huit", 8),
+            ("neuf", 9),
+        ];
+        let scanner = Scanner::new(DIGITS.iter().chain(FRENCH));
+        assert_eq!(scanner.calibration("zéroseptrois"), Some(3));
+        assert_eq!(scanner.calibration("aquatreuneuf"), Some(49));
+        assert_eq!(scanner.calibration("cinquante"), Some(55));
+        let input = "deux4x\nhuit\nxsixun";
+        assert_eq!(calibrations(input, &scanner), Some(24 + 88 + 61));
+    }
 }
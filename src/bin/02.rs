@@ -1,74 +1,90 @@
 advent_of_code::solution!(2);
 
-use regex::Regex;
-use std::cmp::max;
+use advent_of_code::parse::{parse_lines, ParseResult};
 
-const MAX_RED: u32 = 12;
-const MAX_GREEN: u32 = 13;
-const MAX_BLUE: u32 = 14;
+const COLOURS: &[&str] = &["red", "green", "blue"];
 
-struct Game {
+const BAG: &[(&str, u32)] = &[("red", 12), ("green", 13), ("blue", 14)];
+
+struct Game<'a> {
     id: u32,
-    red: u32,
-    green: u32,
-    blue: u32,
+    /// Cubes shown in each draw, as (colour, count)
+    draws: Vec<Vec<(&'a str, u32)>>,
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    Regex::new(r"(?m)^Game (\d+): (\d+ \w+(?:[,;] \d+ \w+)*)$")
-        .unwrap()
-        .captures_iter(input)
-        .map(|cap| {
-            let mut game = Game {
-                id: cap[1].parse().unwrap(),
-                red: 0,
-                green: 0,
-                blue: 0,
-            };
-            for pick in cap[2].split("; ").map(|s| s.split(", ")).flatten() {
-                let mut split = pick.split(' ');
-                let count = split.next().unwrap().parse().unwrap();
-                let color = split.next().unwrap();
-                match color {
-                    "red" => game.red = max(count, game.red),
-                    "green" => game.green = max(count, game.green),
-                    "blue" => game.blue = max(count, game.blue),
-                    _ => panic!("Unknown color: {}", color),
+/// Parses game records, such as "Game 1: 3 blue, 4 red; 1 red", where draws
+/// may only show cubes of `colours`.
+fn parse<'a>(input: &'a str, colours: &[&str]) -> ParseResult<Vec<Game<'a>>> {
+    parse_lines(input, |c| {
+        c.tag("Game ")?;
+        let id = c.unsigned()?;
+        c.tag(":")?;
+        c.space();
+        let draws = c.sep_by(";", |c| {
+            c.sep_by(",", |c| {
+                let count = c.unsigned()?;
+                c.space();
+                let at = *c;
+                let colour = c.word()?;
+                if !colours.contains(&colour) {
+                    return Err(at.error(format!("unknown colour {colour:?}")));
                 }
-            }
-            game
+                Ok((colour, count))
+            })
+        })?;
+        Ok(Game { id, draws })
+    })
+}
+
+impl Game<'_> {
+    fn cubes(&self) -> impl Iterator<Item = &(&str, u32)> {
+        self.draws.iter().flatten()
+    }
+
+    /// Whether all draws are possible from a bag with `limits` cubes of each
+    /// colour, and none of the colours not listed.
+    fn possible(&self, limits: &[(&str, u32)]) -> bool {
+        self.cubes().all(|(colour, count)| {
+            limits
+                .iter()
+                .any(|(c, limit)| c == colour && count <= limit)
         })
-        .filter(|game| game.red <= MAX_RED && game.green <= MAX_GREEN && game.blue <= MAX_BLUE)
+    }
+
+    /// Fewest cubes of each of `colours` needed for all draws.
+    fn minimum_bag(&self, colours: &[&str]) -> Vec<u32> {
+        colours
+            .iter()
+            .map(|colour| {
+                self.cubes()
+                    .filter(|(c, _)| c == colour)
+                    .map(|(_, count)| *count)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+}
+
+fn games(input: &str) -> Option<Vec<Game<'_>>> {
+    parse(input, COLOURS)
+        .map_err(|e| eprintln!("Invalid input: {e}"))
+        .ok()
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    games(input)?
+        .iter()
+        .filter(|game| game.possible(BAG))
         .map(|game| game.id)
         .sum::<u32>()
         .into()
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    Regex::new(r"(?m)^Game (\d+): (\d+ \w+(?:[,;] \d+ \w+)*)$")
-        .unwrap()
-        .captures_iter(input)
-        .map(|cap| {
-            let mut game = Game {
-                id: cap[1].parse().unwrap(),
-                red: 0,
-                green: 0,
-                blue: 0,
-            };
-            for pick in cap[2].split("; ").map(|s| s.split(", ")).flatten() {
-                let mut split = pick.split(' ');
-                let count = split.next().unwrap().parse().unwrap();
-                let color = split.next().unwrap();
-                match color {
-                    "red" => game.red = max(count, game.red),
-                    "green" => game.green = max(count, game.green),
-                    "blue" => game.blue = max(count, game.blue),
-                    _ => panic!("Unknown color: {}", color),
-                }
-            }
-            game
-        })
-        .map(|game| game.red * game.green * game.blue)
+    games(input)?
+        .iter()
+        .map(|game| game.minimum_bag(COLOURS).iter().product::<u32>())
         .sum::<u32>()
         .into()
 }
@@ -88,4 +104,41 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(2286));
     }
+
+    #[test]
+    fn test_games() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let games = parse(&input, COLOURS).unwrap();
+        assert_eq!(games[0].draws.len(), 3);
+        assert_eq!(games[2].minimum_bag(&["red", "blue"]), [20, 6]);
+        assert_eq!(games[0].minimum_bag(&["yellow"]), [0]);
+        assert!(games[0].possible(&[("red", 4), ("green", 2), ("blue", 6)]));
+        assert!(!games[0].possible(&[("red", 4), ("blue", 6)]));
+        assert!(!games[0].possible(&[("red", 3), ("green", 2), ("blue", 6)]));
+
+        // Other colours
+        let input = "Game 7: 2 yellow, 1 red; 5 yellow\n";
+        assert!(parse(input, COLOURS).is_err());
+        let games = parse(input, &["red", "yellow"]).unwrap();
+        assert_eq!(games[0].minimum_bag(&["yellow", "red"]), [5, 1]);
+        assert!(games[0].possible(&[("yellow", 5), ("red", 1)]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |input| parse(input, COLOURS).err().unwrap().to_string();
+        assert_eq!(
+            error("Game 1: 1 red\nGame 2: 3 purple, 1 red\n"),
+            "line 2, column 11: unknown colour \"purple\""
+        );
+        assert_eq!(
+            error("Game 1: 1 red\n\nGame 3 2 blue\n"),
+            "line 3, column 7: expected \":\", found ' '"
+        );
+        assert_eq!(
+            error("Game 1: 1 red; blue\n"),
+            "line 1, column 16: expected an unsigned integer, found 'b'"
+        );
+        assert_eq!(part_one("Game 1: 1 red, 2 pink\n"), None);
+    }
 }
@@ -1,79 +1,146 @@
 advent_of_code::solution!(3);
 
-use regex::Regex;
-use std::{
-    cmp::{max, min},
-    collections::HashMap,
-};
+use std::collections::HashMap;
+use std::ops::Range;
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let schematic = input.lines().collect::<Vec<_>>();
-    let part_re = Regex::new(r"\d+").unwrap();
-    let symbol_re = Regex::new(r"[^0-9.]").unwrap();
-    let mut sum = 0;
-    for (row, content) in schematic.iter().enumerate() {
-        for part in part_re.find_iter(content) {
-            // check for symbols up from part
-            if row > 0
-                && symbol_re.is_match(
-                    &schematic[row - 1]
-                        [max(part.start(), 1) - 1..min(part.end() + 1, content.len())],
-                )
-                || row < schematic.len() - 1
-                    && symbol_re.is_match(
-                        &schematic[row + 1]
-                            [max(part.start(), 1) - 1..min(part.end() + 1, content.len())],
-                    )
-                || symbol_re.is_match(
-                    &schematic[row][max(part.start(), 1) - 1..min(part.end() + 1, content.len())],
-                )
-            {
-                sum += part.as_str().parse::<u32>().unwrap();
-            }
-        }
-    }
-    Some(sum)
+struct Number {
+    value: u32,
+    row: usize,
+    span: Range<usize>,
+    /// Adjacent symbols, as indices in the schematic's symbols
+    symbols: Vec<usize>,
 }
 
-pub fn part_two(input: &str) -> Option<u32> {
-    let schematic = input.lines().collect::<Vec<_>>();
-    let part_re = Regex::new(r"\d+").unwrap();
-    let gear_re = Regex::new(r"\*").unwrap();
-    // Map of gear (x, y) to times they've been used and values
-    let mut gear_map: HashMap<(isize, isize), (u32, u32)> = HashMap::new();
-    for (row, content) in schematic.iter().enumerate() {
-        for gear in gear_re.find_iter(content) {
-            gear_map.insert((row as isize, gear.start() as isize), (0, 0));
+struct Symbol {
+    kind: char,
+    /// Adjacent numbers, as indices in the schematic's numbers
+    numbers: Vec<usize>,
+}
+
+struct Schematic<'a> {
+    lines: Vec<&'a str>,
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+}
+
+impl<'a> Schematic<'a> {
+    /// Any character other than a digit or `.` is a symbol.
+    fn parse(input: &'a str) -> Self {
+        let lines = input.lines().collect::<Vec<_>>();
+        let number_re = advent_of_code::regex!(r"\d+");
+        let mut numbers = vec![];
+        let mut symbols = vec![];
+        let mut positions = HashMap::new();
+        for (row, line) in lines.iter().enumerate() {
+            for (col, kind) in line.char_indices() {
+                if kind != '.' && !kind.is_ascii_digit() {
+                    positions.insert((row, col), symbols.len());
+                    symbols.push(Symbol {
+                        kind,
+                        numbers: vec![],
+                    });
+                }
+            }
+            for m in number_re.find_iter(line) {
+                numbers.push(Number {
+                    value: m.as_str().parse().unwrap(),
+                    row,
+                    span: m.range(),
+                    symbols: vec![],
+                });
+            }
         }
-    }
-    let mut sum = 0;
-    let mut update_sum = |sum: &mut u32, part: &str, row: isize, col: isize| {
-        if let Some((times, value)) = gear_map.get_mut(&(row, col)) {
-            *times += 1;
-            // Gear should only be used twice, not more
-            match *times {
-                1 => *value = part.parse::<u32>().unwrap(),
-                2 => *sum += *value * part.parse::<u32>().unwrap(),
-                3 => *sum -= *value * part.parse::<u32>().unwrap(),
-                _ => return,
+        // Symbols around each number, diagonals included
+        for (i, number) in numbers.iter_mut().enumerate() {
+            for row in number.row.saturating_sub(1)..=number.row + 1 {
+                for col in number.span.start.saturating_sub(1)..=number.span.end {
+                    if let Some(s) = positions.get(&(row, col)) {
+                        number.symbols.push(*s);
+                        symbols[*s].numbers.push(i);
+                    }
+                }
             }
         }
-    };
-    for (row, content) in schematic.iter().enumerate() {
-        let row = row as isize;
-        for part in part_re.find_iter(content) {
-            let start = part.start() as isize;
-            let end = part.end() as isize;
-            // Check for gears around part
-            for col in start - 1..end + 1 {
-                update_sum(&mut sum, part.as_str(), row - 1, col);
-                update_sum(&mut sum, part.as_str(), row + 1, col);
+        Self {
+            lines,
+            numbers,
+            symbols,
+        }
+    }
+
+    /// Whether `number` is adjacent to a symbol among `kinds`, or to any
+    /// symbol if `None`.
+    fn is_part(&self, number: &Number, kinds: Option<&str>) -> bool {
+        number
+            .symbols
+            .iter()
+            .any(|s| kinds.is_none_or(|k| k.contains(self.symbols[*s].kind)))
+    }
+
+    fn part_numbers<'s>(&'s self, kinds: Option<&'s str>) -> impl Iterator<Item = &'s Number> {
+        self.numbers.iter().filter(move |n| self.is_part(n, kinds))
+    }
+
+    /// Symbols of `kind` with exactly `neighbours` adjacent numbers.
+    fn gears(&self, kind: char, neighbours: usize) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .iter()
+            .filter(move |s| s.kind == kind && s.numbers.len() == neighbours)
+    }
+
+    /// Values of the numbers adjacent to `symbol`.
+    fn values<'s>(&'s self, symbol: &'s Symbol) -> impl Iterator<Item = u32> + 's {
+        symbol.numbers.iter().map(|n| self.numbers[*n].value)
+    }
+
+    /// Sum of the values around each symbol of `kind`.
+    #[allow(dead_code)]
+    fn sums(&self, kind: char) -> Vec<u32> {
+        self.symbols
+            .iter()
+            .filter(|s| s.kind == kind)
+            .map(|s| self.values(s).sum())
+            .collect()
+    }
+
+    /// The schematic with, after each line, its numbers marked with `+` if
+    /// `counted` or `-` otherwise.
+    fn render(&self, counted: impl Fn(&Number) -> bool) -> String {
+        let mut out = String::new();
+        for (row, line) in self.lines.iter().enumerate() {
+            out += line;
+            for n in self.numbers.iter().filter(|n| n.row == row) {
+                let mark = if counted(n) { '+' } else { '-' };
+                out += &format!(" {mark}{}", n.value);
             }
-            update_sum(&mut sum, part.as_str(), row, start - 1);
-            update_sum(&mut sum, part.as_str(), row, end);
+            out.push('\n');
         }
+        out
     }
-    Some(sum)
+}
+
+#[allow(dead_code)]
+fn print_schematic(schematic: &Schematic, kinds: Option<&str>) {
+    print!("{}", schematic.render(|n| schematic.is_part(n, kinds)));
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    let schematic = Schematic::parse(input);
+    // print_schematic(&schematic, None);
+    schematic
+        .part_numbers(None)
+        .map(|n| n.value)
+        .sum::<u32>()
+        .into()
+}
+
+pub fn part_two(input: &str) -> Option<u32> {
+    let schematic = Schematic::parse(input);
+    schematic
+        .gears('*', 2)
+        .map(|g| schematic.values(g).product::<u32>())
+        .sum::<u32>()
+        .into()
 }
 
 #[cfg(test)]
@@ -91,4 +158,26 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(467835));
     }
+
+    #[test]
+    fn test_schematic() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let schematic = Schematic::parse(&input);
+        let values = |kinds| {
+            schematic
+                .part_numbers(kinds)
+                .map(|n| n.value)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(Some("#")), [633]);
+        assert_eq!(values(Some("$+")), [592, 664]);
+        assert_eq!(schematic.gears('*', 1).count(), 1);
+        assert_eq!(schematic.gears('#', 1).count(), 1);
+        assert_eq!(schematic.sums('*'), [502, 617, 1353]);
+        let render = schematic.render(|n| !n.symbols.is_empty());
+        let lines = render.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "467..114.. +467 -114");
+        assert_eq!(lines[5], ".....+.58. -58");
+        assert_eq!(lines[1], "...*......");
+    }
 }
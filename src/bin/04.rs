@@ -1,10 +1,9 @@
 advent_of_code::solution!(4);
 
-use regex::Regex;
 use std::collections::HashSet;
 
 pub fn part_one(input: &str) -> Option<u32> {
-    let card_re = Regex::new(r"Card +\d+:([\d ]+)\|([\d ]+)").unwrap();
+    let card_re = advent_of_code::regex!(r"Card +\d+:([\d ]+)\|([\d ]+)");
     let as_set = |s: &str| {
         s.split(' ')
             .filter_map(|s| s.parse::<u32>().ok())
@@ -28,7 +27,7 @@ pub fn part_one(input: &str) -> Option<u32> {
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let card_re = Regex::new(r"Card +\d+:([\d ]+)\|([\d ]+)").unwrap();
+    let card_re = advent_of_code::regex!(r"Card +\d+:([\d ]+)\|([\d ]+)");
     let as_set = |s: &str| {
         s.split(' ')
             .filter_map(|s| s.parse::<u32>().ok())
@@ -1,134 +1,234 @@
 advent_of_code::solution!(5);
 
-use regex::Regex;
+use advent_of_code::parse::{lines, Cursor, ParseError, ParseResult};
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 
-const FORMAT: &str = r"seeds: ([\d ]+)
+/// A mapping of all numbers, by pieces: numbers from the start of a piece up
+/// to the start of the next one are shifted by the same amount. The first
+/// piece starts at 0, and the last one is unbounded and never shifted.
+#[derive(Debug, Clone, PartialEq)]
+struct Map {
+    pieces: Vec<(u64, i64)>,
+}
 
-seed-to-soil map:
-([\d\s]+)
+impl Map {
+    fn identity() -> Self {
+        Self {
+            pieces: vec![(0, 0)],
+        }
+    }
 
-soil-to-fertilizer map:
-([\d\s]+)
+    /// The map sending `src..src + len` to `dst..dst + len` for each
+    /// `(dst, src, len)`, and other numbers to themselves.
+    fn from_ranges(ranges: &[(u64, u64, u64)]) -> Self {
+        let mut ranges = ranges.to_vec();
+        ranges.sort_by_key(|(_, src, _)| *src);
+        let mut pieces = vec![(0, 0)];
+        for (dst, src, len) in ranges {
+            pieces.push((src, dst as i64 - src as i64));
+            pieces.push((src + len, 0));
+        }
+        Self::merged(pieces)
+    }
 
-fertilizer-to-water map:
-([\d\s]+)
+    /// Pieces sorted by start, keeping the last of those starting together,
+    /// and merged when consecutive ones have the same shift.
+    fn merged(mut pieces: Vec<(u64, i64)>) -> Self {
+        pieces.reverse();
+        pieces.dedup_by_key(|(start, _)| *start);
+        pieces.reverse();
+        pieces.dedup_by_key(|(_, shift)| *shift);
+        Self { pieces }
+    }
 
-water-to-light map:
-([\d\s]+)
+    /// Pieces with their end, or `None` for the last one.
+    fn bounded(&self) -> impl Iterator<Item = (u64, Option<u64>, i64)> + '_ {
+        self.pieces
+            .iter()
+            .enumerate()
+            .map(|(i, (start, shift))| (*start, self.pieces.get(i + 1).map(|p| p.0), *shift))
+    }
 
-light-to-temperature map:
-([\d\s]+)
+    fn get(&self, n: u64) -> u64 {
+        let i = self.pieces.partition_point(|(start, _)| *start <= n) - 1;
+        n.checked_add_signed(self.pieces[i].1).unwrap()
+    }
 
-temperature-to-humidity map:
-([\d\s]+)
+    /// Numbers `range` is mapped to, as disjoint ranges.
+    fn image(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        self.bounded()
+            .filter_map(|(start, end, shift)| {
+                let from = range.start.max(start);
+                let to = end.map_or(range.end, |end| range.end.min(end));
+                let shifted = |n: u64| n.checked_add_signed(shift).unwrap();
+                (from < to).then(|| shifted(from)..shifted(to))
+            })
+            .collect()
+    }
 
-humidity-to-location map:
-([\d\s]+)
-";
+    /// This map followed by `next`, as a single map.
+    fn then(&self, next: &Map) -> Map {
+        // Split each piece where its image crosses a piece of `next`
+        let mut pieces = vec![];
+        for (start, end, shift) in self.bounded() {
+            let image_start = start.checked_add_signed(shift).unwrap();
+            let image_end = end.map(|end| end.checked_add_signed(shift).unwrap());
+            for (next_start, next_end, next_shift) in next.bounded() {
+                let overlaps = next_end.is_none_or(|e| e > image_start)
+                    && image_end.is_none_or(|e| e > next_start);
+                if overlaps {
+                    let from = image_start.max(next_start);
+                    pieces.push((from.checked_add_signed(-shift).unwrap(), shift + next_shift));
+                }
+            }
+        }
+        Self::merged(pieces)
+    }
 
-pub fn parse(input: &str) -> (Vec<u64>, Vec<Vec<Vec<u64>>>) {
-    let re = Regex::new(FORMAT).unwrap();
-    let captures = re.captures(input).unwrap();
-    let extract_map = |cap: &str| {
-        let mut v = cap
-            .split('\n')
-            .map(|s| {
-                s.split(' ')
-                    .filter_map(|s| s.parse::<u64>().ok())
-                    .collect::<Vec<_>>()
+    /// The inverse map, if this one is a bijection.
+    fn inverse(&self) -> Option<Map> {
+        let mut images = self
+            .bounded()
+            .map(|(start, end, shift)| {
+                let image = |n: u64| n.checked_add_signed(shift).unwrap();
+                (image(start), end.map(image), -shift)
             })
             .collect::<Vec<_>>();
-        v.sort_by_key(|a| a[1]);
-        v
-    };
-
-    let seeds = captures[1]
-        .split(' ')
-        .filter_map(|s| s.parse::<u64>().ok())
-        .collect::<Vec<_>>();
+        images.sort_by_key(|(start, ..)| *start);
+        // Images must tile all numbers, each one ending where the next starts
+        let tiled = images[0].0 == 0
+            && images.windows(2).all(|w| w[0].1 == Some(w[1].0))
+            && images.last().unwrap().1.is_none();
+        tiled.then(|| Self::merged(images.iter().map(|(s, _, shift)| (*s, *shift)).collect()))
+    }
+}
 
-    let maps = (2..9)
-        .map(|i| extract_map(&captures[i]))
-        .collect::<Vec<_>>();
+/// Numbers separated by spaces, up to the end of the line.
+fn numbers(c: &mut Cursor) -> ParseResult<Vec<u64>> {
+    let mut numbers = vec![];
+    c.space();
+    while !c.is_empty() {
+        numbers.push(c.unsigned()?);
+        c.space();
+    }
+    Ok(numbers)
+}
 
-    (seeds, maps)
+struct Almanac<'a> {
+    seeds: Vec<u64>,
+    /// Map from one category to another, by source category
+    maps: HashMap<&'a str, Vec<(&'a str, Map)>>,
 }
 
-pub fn part_one(input: &str) -> Option<u64> {
-    let (seeds, maps) = parse(input);
-    seeds
-        .iter()
-        .map(|s| {
-            let mut r = *s;
-            for m in &maps {
-                let mut i = 0; // assuming there's at least one rule
-                while i + 1 < m.len() && m[i + 1][1] <= r {
-                    i += 1;
-                }
-                if r >= m[i][1] && r < m[i][1] + m[i][2] {
-                    r = m[i][0] + (r - m[i][1]);
+impl<'a> Almanac<'a> {
+    fn parse(input: &'a str) -> ParseResult<Self> {
+        let mut seeds = None;
+        let mut maps: HashMap<&str, Vec<(&str, Map)>> = HashMap::new();
+        let mut ranges = vec![];
+        let mut section = None;
+        let mut close = |section: Option<(&'a str, &'a str)>, ranges: &mut Vec<_>| {
+            if let Some((from, to)) = section {
+                let map = Map::from_ranges(ranges);
+                maps.entry(from).or_default().push((to, map));
+                ranges.clear();
+            }
+        };
+        for (n, line) in lines(input) {
+            let mut c = Cursor::at_line(line, n);
+            if c.opt_tag("seeds:") {
+                c.space();
+                seeds = Some(numbers(&mut c)?);
+            } else if c.rest().starts_with(char::is_alphabetic) {
+                close(section, &mut ranges);
+                let from = c.word()?;
+                c.tag("-to-")?;
+                let to = c.word()?;
+                c.space();
+                c.tag("map:")?;
+                section = Some((from, to));
+            } else if section.is_some() {
+                let mut range = numbers(&mut c)?.into_iter();
+                match (range.next(), range.next(), range.next(), range.next()) {
+                    (Some(dst), Some(src), Some(len), None) => ranges.push((dst, src, len)),
+                    _ => return Err(Cursor::at_line(line, n).error("expected 3 numbers")),
                 }
+            } else {
+                return Err(c.error("expected a map header first"));
             }
-            r
-        })
-        .min()
-}
-
-pub fn part_two(input: &str) -> Option<u64> {
-    let (seeds, maps) = parse(input);
-    let mut open_intervals = seeds
-        .chunks(2)
-        .map(|s| (0_usize, s[0], s[0] + s[1]))
-        .collect::<Vec<_>>();
-    // Find the minimum start amongs all final intervals
-    let mut final_intervals: Vec<(u64, u64)> = Vec::new();
-    while open_intervals.len() > 0 {
-        // Break up the interval into smaller intervals
-        let (map, start, end) = open_intervals.pop().unwrap();
-        if map == maps.len() {
-            // This interval has been translated by all maps
-            final_intervals.push((start, end));
-            continue;
+            c.end()?;
         }
-        let m = &maps[map];
-        let mut i = 0;
-        let mut s = start; // break intervals from the start
-        while i < m.len() && end > m[i][1] {
-            // end is after this mapping's start
-            if s >= m[i][1] + m[i][2] {
-                // start is after this mapping's end -> next mapping
-                i += 1;
-            } else {
-                // start is before this mapping's end
-                if s < m[i][1] {
-                    // start is before this mapping's start -> split and stay on mapping
-                    open_intervals.push((map + 1, s, m[i][1]));
-                    s = m[i][1];
-                } else {
-                    // start is within this mapping's range
-                    if end <= m[i][1] + m[i][2] {
-                        // end is also within this mapping's range -> translate and stop
-                        open_intervals.push((
-                            map + 1,
-                            m[i][0] + (s - m[i][1]),
-                            m[i][0] + (end - m[i][1]),
-                        ));
-                        break;
-                    } else {
-                        // end is after this mapping's range -> translate, split and continue
-                        open_intervals.push((map + 1, m[i][0] + (s - m[i][1]), m[i][0] + m[i][2]));
-                        s = m[i][1] + m[i][2];
-                        i += 1;
-                    }
+        close(section, &mut ranges);
+        let seeds = seeds.ok_or(ParseError {
+            line: 1,
+            column: 1,
+            message: "missing seeds".to_string(),
+        })?;
+        Ok(Self { seeds, maps })
+    }
+
+    /// Categories from `from` to `to` following maps, if connected.
+    fn path(&self, from: &'a str, to: &str) -> Option<Vec<&'a str>> {
+        let mut previous = HashMap::from([(from, from)]);
+        let mut open = VecDeque::from([from]);
+        while let Some(here) = open.pop_front() {
+            if here == to {
+                let mut path = vec![here];
+                while *path.last().unwrap() != from {
+                    path.push(previous[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (next, _) in self.maps.get(here).into_iter().flatten() {
+                if !previous.contains_key(next) {
+                    previous.insert(next, here);
+                    open.push_back(next);
                 }
             }
         }
-        if i == m.len() {
-            // interval was beyond any mapping -> continue
-            open_intervals.push((map + 1, s, end));
+        None
+    }
+
+    /// All maps along the way composed into one, going backwards through
+    /// inverted maps if there is no way forward.
+    fn mapping(&self, from: &'a str, to: &'a str) -> Option<Map> {
+        if let Some(path) = self.path(from, to) {
+            return self.compose(&path);
         }
+        self.compose(&self.path(to, from)?)?.inverse()
     }
-    final_intervals.iter().map(|(s, _)| s).min().copied()
+
+    fn compose(&self, path: &[&str]) -> Option<Map> {
+        path.windows(2).try_fold(Map::identity(), |map, step| {
+            let (_, next) = self.maps[step[0]].iter().find(|(to, _)| *to == step[1])?;
+            Some(map.then(next))
+        })
+    }
+}
+
+fn parse(input: &str) -> Option<Almanac<'_>> {
+    Almanac::parse(input)
+        .map_err(|e| eprintln!("Invalid input: {e}"))
+        .ok()
+}
+
+pub fn part_one(input: &str) -> Option<u64> {
+    let almanac = parse(input)?;
+    let map = almanac.mapping("seed", "location")?;
+    almanac.seeds.iter().map(|s| map.get(*s)).min()
+}
+
+pub fn part_two(input: &str) -> Option<u64> {
+    // Seeds come as ranges, mapped by pieces into several ranges of locations
+    let almanac = parse(input)?;
+    let map = almanac.mapping("seed", "location")?;
+    almanac
+        .seeds
+        .chunks(2)
+        .flat_map(|s| map.image(s[0]..s[0] + s[1]))
+        .map(|r| r.start)
+        .min()
 }
 
 #[cfg(test)]
@@ -146,4 +246,55 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(46));
     }
+
+    #[test]
+    fn test_map() {
+        let map = Map::from_ranges(&[(50, 98, 2), (52, 50, 48)]);
+        let values = [0, 49, 50, 97, 98, 99, 100].map(|n| map.get(n));
+        assert_eq!(values, [0, 49, 52, 99, 50, 51, 100]);
+        assert_eq!(map.image(45..100), [45..50, 52..100, 50..52]);
+        let inverse = map.inverse().unwrap();
+        assert!((0..120).all(|n| inverse.get(map.get(n)) == n));
+        assert_eq!(map.then(&inverse), Map::identity());
+        // Two numbers sent to 10
+        assert_eq!(Map::from_ranges(&[(10, 0, 1)]).inverse(), None);
+    }
+
+    #[test]
+    fn test_almanac() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let almanac = Almanac::parse(&input).unwrap();
+        let path = almanac.path("seed", "location").unwrap();
+        assert_eq!(path.len(), 8);
+        let humidity = almanac.mapping("seed", "humidity").unwrap();
+        assert_eq!([79, 14, 55, 13].map(|s| humidity.get(s)), [78, 43, 82, 35]);
+        let seed = almanac.mapping("location", "seed").unwrap();
+        assert_eq!([82, 43, 86, 35].map(|l| seed.get(l)), [79, 14, 55, 13]);
+        assert!(almanac.mapping("seed", "nowhere").is_none());
+
+        // Sections in any order, and any category names
+        let input =
+            "seeds: 1 5\n\nb-to-c map:\n20 10 10\n10 20 10\n\na-to-b map:\n10 0 10\n0 10 10\n";
+        let almanac = Almanac::parse(input).unwrap();
+        let map = almanac.mapping("a", "c").unwrap();
+        assert_eq!(
+            almanac
+                .seeds
+                .iter()
+                .map(|s| map.get(*s))
+                .collect::<Vec<_>>(),
+            [21, 25]
+        );
+        assert_eq!(almanac.mapping("c", "a").unwrap().get(25), 5);
+        // Maps sending two numbers to one have no inverse
+        let input = "seeds: 1\n\na-to-b map:\n10 0 10\n";
+        assert!(Almanac::parse(input).unwrap().mapping("b", "a").is_none());
+
+        let err = Almanac::parse("seeds: 1\n\na-to-b map:\n1 2\n")
+            .err()
+            .unwrap();
+        assert_eq!((err.line, err.column), (4, 1));
+        let err = Almanac::parse("seeds: 1\na-to map:\n").err().unwrap();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
 }
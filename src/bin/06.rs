@@ -1,55 +1,76 @@
 advent_of_code::solution!(6);
 
+use num::{BigUint, Integer, One, ToPrimitive, Zero};
+use std::ops::RangeInclusive;
+
 const RE: &str = r"Time: ([\d\s]+)
 Distance: ([\d\s]+)
 ";
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let captures = regex::Regex::new(RE).unwrap().captures(input).unwrap();
-    let times = captures[1]
-        .split(' ')
-        .filter_map(|s| s.parse::<u32>().ok())
-        .collect::<Vec<_>>();
-    let dists = captures[2]
-        .split(' ')
-        .filter_map(|s| s.parse::<u32>().ok())
-        .collect::<Vec<_>>();
-    let mut mul = 1;
-    for i in 0..times.len() {
-        let mut sum = 0;
-        for j in 0..times[i] {
-            if j * (times[i] - j) > dists[i] {
-                sum += 1;
-            }
+/// Times and record distances, either as separate races or as a single race
+/// with all digits put together.
+fn parse(input: &str, kerning: bool) -> Vec<(BigUint, BigUint)> {
+    let captures = advent_of_code::regex!(RE).captures(input).unwrap();
+    let numbers = |i: usize| match kerning {
+        true => vec![captures[i]
+            .replace(char::is_whitespace, "")
+            .parse()
+            .unwrap()],
+        false => captures[i]
+            .split_whitespace()
+            .map(|s| s.parse().unwrap())
+            .collect::<Vec<BigUint>>(),
+    };
+    numbers(1).into_iter().zip(numbers(2)).collect()
+}
+
+/// Hold times that beat the `record` distance in a race of `time`, if any.
+fn winning(time: &BigUint, record: &BigUint) -> Option<RangeInclusive<BigUint>> {
+    // Holding for h goes h * (time - h), which beats the record when
+    // h^2 - time * h + record < 0, i.e. when x = time - 2h verifies
+    // x^2 < time^2 - 4 * record. Hold times are integers, so x has the same
+    // parity as time, and the record is exactly hit when x^2 is equal.
+    let squared = time * time;
+    let four_records = record * 4_u32;
+    if squared <= four_records {
+        return None;
+    }
+    let discriminant = squared - four_records;
+    let root = discriminant.sqrt();
+    // Largest x with x^2 < discriminant and the parity of time
+    let mut x = match &root * &root == discriminant {
+        true => root - 1_u32,
+        false => root,
+    };
+    if x.is_odd() != time.is_odd() {
+        if x.is_zero() {
+            return None;
         }
-        mul *= sum;
+        x -= 1_u32;
     }
-    Some(mul)
+    Some((time - &x) / 2_u32..=(time + &x) / 2_u32)
+}
+
+fn ways(time: &BigUint, record: &BigUint) -> BigUint {
+    winning(time, record).map_or(BigUint::zero(), |hold| {
+        hold.end() - hold.start() + BigUint::one()
+    })
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    parse(input, false)
+        .iter()
+        .map(|(time, record)| ways(time, record))
+        .product::<BigUint>()
+        .to_u32()
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
-    let captures = regex::Regex::new(RE).unwrap().captures(input).unwrap();
-    let time = captures[1].replace(" ", "").parse::<u64>().unwrap();
-    let dist = captures[2].replace(" ", "").parse::<u64>().unwrap();
-    // I orginally brute-forced this as above (easy with Rust)...
-    // let mut sum = 0;
-    // for j in (dist/time)..time {
-    //     if j * (time - j) > dist {
-    //         sum += 1;
-    //     }
-    // }
-    // And after, only, thought to use the quadratic forumla:
-    // j * (time - j) > dist
-    // j^2 - time * j + dist < 0
-    // The values of j for which == 0 are the bounds:
-    // j = (time +- sqrt(time^2 - 4 * dist)) / 2
-    let j_lo =
-        (time as f64 - ((time as f64).powi(2) - 4.0 * dist as f64).sqrt() / 2.0).ceil() as u64;
-    let j_hi =
-        (time as f64 + ((time as f64).powi(2) - 4.0 * dist as f64).sqrt() / 2.0).ceil() as u64;
-    // The crazy part is that the brute force version is only 500 times slower (in release mode),
-    // so I guess computing those square roots is expensive.
-    Some(j_hi - j_lo)
+    parse(input, true)
+        .iter()
+        .map(|(time, record)| ways(time, record))
+        .product::<BigUint>()
+        .to_u64()
 }
 
 #[cfg(test)]
@@ -67,4 +88,40 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(71503));
     }
+
+    #[test]
+    fn test_winning() {
+        let race = |time: u64, record: u64| {
+            winning(&BigUint::from(time), &BigUint::from(record))
+                .map(|hold| (hold.start().to_u64().unwrap(), hold.end().to_u64().unwrap()))
+        };
+        assert_eq!(race(7, 9), Some((2, 5)));
+        assert_eq!(race(15, 40), Some((4, 11)));
+        // The record is hit exactly holding 10 or 20
+        assert_eq!(race(30, 200), Some((11, 19)));
+        assert_eq!(race(4, 3), Some((2, 2)));
+        assert_eq!(race(4, 4), None);
+        assert_eq!(race(5, 6), None);
+        assert_eq!(race(0, 0), None);
+        for time in 0..40 {
+            for record in 0..400 {
+                let expected = (0..=time)
+                    .filter(|h| h * (time - h) > record)
+                    .collect::<Vec<_>>();
+                let bounds = expected.first().zip(expected.last());
+                assert_eq!(race(time, record), bounds.map(|(a, b)| (*a, *b)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_race() {
+        // Record hit exactly holding 10^30, beyond u64 and f64 precision
+        let hold = BigUint::from(10_u32).pow(30);
+        let time = &hold * 3_u32 + 1_u32;
+        let record = &hold * (&time - &hold);
+        let winning = winning(&time, &record).unwrap();
+        assert_eq!(winning, &hold + 1_u32..=&time - &hold - 1_u32);
+        assert_eq!(ways(&time, &record), hold);
+    }
 }
@@ -1,112 +1,130 @@
 advent_of_code::solution!(7);
 
-use regex::Regex;
 use std::collections::HashMap;
 
-const FORMAT: &str = r"([AKQJT98765432]+) (\d+)";
+const FORMAT: &str = r"(\S+) (\d+)";
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let re = Regex::new(FORMAT).unwrap();
-    let mut bids = re
-        .captures_iter(input)
-        .map(|caps| caps.extract::<2>())
-        .map(|(_, cap)| {
-            let mut freq = HashMap::new();
-            for c in cap[0].chars() {
-                freq.entry(c).and_modify(|e| *e += 1).or_insert(1);
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
+
+/// Five-card poker hand types, from group sizes in decreasing order.
+fn poker_kind(groups: &[usize]) -> u64 {
+    let kind = match groups {
+        [5, ..] => HandType::FiveOfAKind,
+        [4, ..] => HandType::FourOfAKind,
+        [3, 2, ..] => HandType::FullHouse,
+        [3, ..] => HandType::ThreeOfAKind,
+        [2, 2, ..] => HandType::TwoPair,
+        [2, ..] => HandType::OnePair,
+        _ => HandType::HighCard,
+    };
+    kind as u64
+}
+
+/// Hand types for hands of any length: larger groups first, then the next
+/// ones, so that e.g. a full house beats three of a kind. For five cards, this
+/// is the same order as `poker_kind`. Hands may have up to 15 cards.
+#[allow(dead_code)]
+fn group_kind(groups: &[usize]) -> u64 {
+    // Group sizes as digits, in a base larger than any of them, padded to as
+    // many digits as there are cards.
+    let len = groups.iter().sum::<usize>();
+    let base = len as u64 + 1;
+    groups
+        .iter()
+        .chain(std::iter::repeat(&0))
+        .take(len)
+        .fold(0, |acc, g| acc * base + *g as u64)
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {
+    kind: u64,
+    /// Card strengths, in the order of the hand
+    cards: Vec<usize>,
+}
+
+struct Game<'a> {
+    /// Cards from weakest to strongest
+    order: &'a str,
+    /// Cards that join the largest group of other cards
+    wildcards: &'a str,
+    /// Hand type from group sizes in decreasing order
+    kind: fn(&[usize]) -> u64,
+}
+
+const CAMEL_CARDS: Game = Game {
+    order: "23456789TJQKA",
+    wildcards: "",
+    kind: poker_kind,
+};
+
+const JOKERS: Game = Game {
+    order: "J23456789TQKA",
+    wildcards: "J",
+    kind: poker_kind,
+};
+
+impl Game<'_> {
+    /// Classifies a hand, or `None` if it has cards outside of the game.
+    fn classify(&self, hand: &str) -> Option<Hand> {
+        let cards = hand
+            .chars()
+            .map(|c| self.order.find(c))
+            .collect::<Option<Vec<_>>>()?;
+        let mut freq = HashMap::new();
+        let mut wild = 0;
+        for c in hand.chars() {
+            if self.wildcards.contains(c) {
+                wild += 1;
+            } else {
+                *freq.entry(c).or_insert(0) += 1;
             }
-            let kind = match freq.len() {
-                5 => 0, // High card
-                4 => 1, // One pair
-                3 => {
-                    match freq.values().max() {
-                        Some(2) => 2, // Two pair
-                        _ => 3,       // Three of a kind
-                    }
-                }
-                2 => {
-                    match freq.values().max() {
-                        Some(3) => 4, // Full house
-                        _ => 5,       // Four of a kind
-                    }
-                }
-                _ => 6, // Five of a kind
-            };
-            let value = cap[0]
-                .chars()
-                .map(|c| match c {
-                    'A' => 14,
-                    'K' => 13,
-                    'Q' => 12,
-                    'J' => 11,
-                    'T' => 10,
-                    _ => c.to_digit(10).unwrap(),
-                })
-                .enumerate()
-                .map(|(i, v)| v * 15_u32.pow(4 - i as u32))
-                .sum::<u32>();
-            (cap[0], cap[1].parse::<u32>().unwrap(), kind, value)
+        }
+        let mut groups = freq.into_values().collect::<Vec<usize>>();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        match groups.first_mut() {
+            Some(largest) => *largest += wild,
+            None => groups.push(wild),
+        }
+        Some(Hand {
+            kind: (self.kind)(&groups),
+            cards,
         })
-        .collect::<Vec<_>>();
-    bids.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.3.cmp(&b.3)));
-    bids.iter()
-        .enumerate()
-        .map(|(i, bid)| bid.1 * (i + 1) as u32)
-        .sum::<u32>()
-        .into()
+    }
+
+    /// Total winnings of hands with their bids, ranked from weakest.
+    fn winnings(&self, input: &str) -> Option<u32> {
+        let re = advent_of_code::regex!(FORMAT);
+        let mut bids = re
+            .captures_iter(input)
+            .map(|caps| caps.extract::<2>())
+            .map(|(_, cap)| Some((self.classify(cap[0])?, cap[1].parse::<u32>().ok()?)))
+            .collect::<Option<Vec<_>>>()?;
+        bids.sort();
+        bids.iter()
+            .enumerate()
+            .map(|(i, bid)| bid.1 * (i + 1) as u32)
+            .sum::<u32>()
+            .into()
+    }
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    CAMEL_CARDS.winnings(input)
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let re = Regex::new(FORMAT).unwrap();
-    let mut bids = re
-        .captures_iter(input)
-        .map(|caps| caps.extract::<2>())
-        .map(|(_, cap)| {
-            let mut freq = HashMap::new();
-            for c in cap[0].chars() {
-                freq.entry(c).and_modify(|e| *e += 1).or_insert(1);
-            }
-            let jokers = freq.remove(&'J').unwrap_or(0);
-            freq.values_mut().max().map(|v| *v += jokers);
-            let kind = match freq.len() {
-                5 => 0, // High card
-                4 => 1, // One pair
-                3 => {
-                    match freq.values().max() {
-                        Some(2) => 2, // Two pair
-                        _ => 3,       // Three of a kind
-                    }
-                }
-                2 => {
-                    match freq.values().max() {
-                        Some(3) => 4, // Full house
-                        _ => 5,       // Four of a kind
-                    }
-                }
-                _ => 6, // Five of a kind
-            };
-            let value = cap[0]
-                .chars()
-                .map(|c| match c {
-                    'A' => 14,
-                    'K' => 13,
-                    'Q' => 12,
-                    'J' => 1, // Jokers are weakest
-                    'T' => 10,
-                    _ => c.to_digit(10).unwrap(),
-                })
-                .enumerate()
-                .map(|(i, v)| v * 15_u32.pow(4 - i as u32))
-                .sum::<u32>();
-            (cap[0], cap[1].parse::<u32>().unwrap(), kind, value)
-        })
-        .collect::<Vec<_>>();
-    bids.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.3.cmp(&b.3)));
-    bids.iter()
-        .enumerate()
-        .map(|(i, bid)| bid.1 * (i + 1) as u32)
-        .sum::<u32>()
-        .into()
+    // Jokers are weakest on their own, but count as the most frequent card
+    JOKERS.winnings(input)
 }
 
 #[cfg(test)]
@@ -124,4 +142,67 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(5905));
     }
+
+    /// All hands of `len` cards among `cards`.
+    fn hands(cards: &str, len: usize) -> Vec<String> {
+        (0..len).fold(vec![String::new()], |hands, _| {
+            hands
+                .iter()
+                .flat_map(|h| cards.chars().map(move |c| format!("{h}{c}")))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_classify() {
+        fn kind(game: &Game, hand: &str) -> u64 {
+            game.classify(hand).unwrap().kind
+        }
+        assert_eq!(kind(&CAMEL_CARDS, "32T3K"), HandType::OnePair as u64);
+        assert_eq!(kind(&CAMEL_CARDS, "KTJJT"), HandType::TwoPair as u64);
+        assert_eq!(kind(&JOKERS, "KTJJT"), HandType::FourOfAKind as u64);
+        assert_eq!(kind(&JOKERS, "JJJJJ"), HandType::FiveOfAKind as u64);
+        assert_eq!(CAMEL_CARDS.classify("32X3K"), None);
+        // A wildcard hand is as good as the best hand it can stand for
+        for hand in hands("JQ2T", 5) {
+            let best = "Q2T"
+                .chars()
+                .map(|c| kind(&CAMEL_CARDS, &hand.replace('J', &c.to_string())))
+                .max();
+            assert_eq!(Some(kind(&JOKERS, &hand)), best, "{hand}");
+        }
+        // Grouping gives the same order as poker on five cards
+        let grouped = Game {
+            kind: group_kind,
+            ..CAMEL_CARDS
+        };
+        let all = hands("AK98", 5);
+        for (a, b) in all.iter().zip(all.iter().skip(1)) {
+            assert_eq!(
+                CAMEL_CARDS.classify(a).cmp(&CAMEL_CARDS.classify(b)),
+                grouped.classify(a).cmp(&grouped.classify(b)),
+                "{a} {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_longer_hands() {
+        let game = Game {
+            kind: group_kind,
+            ..JOKERS
+        };
+        let ranked = [
+            "2345678", "2245678", "2244678", "2224567", "2224467", "2222345",
+        ];
+        for pair in ranked.windows(2) {
+            assert!(game.classify(pair[0]) < game.classify(pair[1]), "{pair:?}");
+        }
+        // Two pairs and a wildcard: three of a kind beats two pairs
+        assert!(game.classify("22334J5") > game.classify("2233456"));
+        assert_eq!(
+            game.winnings("2222222 10\n22J2222 1\n3456789 100"),
+            Some(132)
+        );
+    }
 }
@@ -1,84 +1,102 @@
 advent_of_code::solution!(8);
 
-use num::integer::lcm;
-use regex::Regex;
+use advent_of_code::progression::{intersect_all, Progression};
 use std::collections::HashMap;
 
-pub fn part_one(input: &str) -> Option<u32> {
+struct Network<'a> {
+    /// Whether each instruction goes left
+    instructions: Vec<bool>,
+    names: Vec<&'a str>,
+    /// Left and right node of each node
+    next: Vec<(usize, usize)>,
+}
+
+fn parse(input: &str) -> Network<'_> {
     let split = input.find("\n\n").unwrap();
-    let instructions = input[0..split].trim().chars().collect::<Vec<_>>();
-    let mut adj_list = Vec::new();
-    let mut vertices = HashMap::new();
-    let re = Regex::new(r"(\w+) = \((\w+), (\w+)\)").unwrap();
-    for (id, (_, [vertex, left, right])) in re
+    let instructions = input[0..split].trim().chars().map(|c| c == 'L').collect();
+    let re = advent_of_code::regex!(r"(\w+) = \((\w+), (\w+)\)");
+    let nodes = re
         .captures_iter(&input[split..])
-        .map(|c| c.extract())
+        .map(|c| c.extract().1)
+        .collect::<Vec<[&str; 3]>>();
+    let ids = nodes
+        .iter()
         .enumerate()
-    {
-        vertices.insert(vertex, id);
-        adj_list.push((left, right));
+        .map(|(id, [name, ..])| (*name, id))
+        .collect::<HashMap<_, _>>();
+    Network {
+        instructions,
+        names: nodes.iter().map(|[name, ..]| *name).collect(),
+        next: nodes
+            .iter()
+            .map(|[_, left, right]| (ids[left], ids[right]))
+            .collect(),
     }
-    let mut pos = "AAA";
-    let mut count = 0;
-    while pos != "ZZZ" {
-        let index = vertices[&pos];
-        match instructions[count % instructions.len()] {
-            'L' => pos = adj_list[index].0,
-            _ => pos = adj_list[index].1,
-        }
-        count += 1;
-    }
-    Some(count as u32)
 }
 
-pub fn part_two(input: &str) -> Option<u64> {
-    let split = input.find("\n\n").unwrap();
-    let instructions = input[0..split].trim().chars().collect::<Vec<_>>();
-    let mut adj_list = Vec::new();
-    let mut vertices = HashMap::new();
-    let re = Regex::new(r"(\w+) = \((\w+), (\w+)\)").unwrap();
-    for (id, (_, [vertex, left, right])) in re
-        .captures_iter(&input[split..])
-        .map(|c| c.extract())
-        .enumerate()
-    {
-        vertices.insert(vertex, id);
-        adj_list.push((left, right));
+impl Network<'_> {
+    fn id(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| *n == name)
     }
-    let mut pos = vertices
-        .keys()
-        .filter(|k| k.ends_with("A"))
-        .collect::<Vec<_>>();
-    let mut periods = vec![0; pos.len()];
-    let mut periods_start = vec![0; pos.len()];
-    let mut count = 0;
-    let mut stop = false;
-    while !stop {
-        for (i, p) in pos.iter_mut().enumerate() {
-            let index = vertices[*p];
-            match instructions[count % instructions.len()] {
-                'L' => *p = &adj_list[index].0,
-                _ => *p = &adj_list[index].1,
+
+    /// Steps at which a walk from `start` is on a node matching `end`.
+    fn walk(&self, start: usize, end: impl Fn(&str) -> bool) -> Vec<Progression> {
+        // The walk is in state (node, instruction index), and cycles once a
+        // state repeats. Ends hit before the cycle are only hit once, ends
+        // within it again each period.
+        let len = self.instructions.len();
+        let mut seen = vec![None; self.names.len() * len];
+        let mut hits = Vec::new();
+        let mut node = start;
+        for step in 0.. {
+            let state = node * len + step % len;
+            if let Some(first) = seen[state] {
+                let period = (step - first) as u64;
+                return hits
+                    .into_iter()
+                    .map(|hit| match hit >= first {
+                        true => (hit as u64, period),
+                        false => (hit as u64, 0),
+                    })
+                    .collect();
             }
-            if p.ends_with("Z") {
-                if periods_start[i] == 0 {
-                    periods_start[i] = count + 1;
-                } else {
-                    if periods[i] == 0 {
-                        periods[i] = count - periods_start[i] + 1;
-                        if periods.iter().filter(|x| **x != 0).count() == periods.len() {
-                            stop = true;
-                        }
-                    }
-                }
+            seen[state] = Some(step);
+            if end(self.names[node]) {
+                hits.push(step);
             }
+            node = match self.instructions[step % len] {
+                true => self.next[node].0,
+                false => self.next[node].1,
+            };
         }
-        count += 1;
+        unreachable!()
+    }
+}
+
+pub fn part_one(input: &str) -> Option<u64> {
+    let network = parse(input);
+    let hits = network.walk(network.id("AAA")?, |n| n == "ZZZ");
+    hits.iter().map(|(first, _)| *first).min()
+}
+
+pub fn part_two(input: &str) -> Option<u64> {
+    // Each ghost hits ..Z nodes at some steps before its walk cycles, and
+    // at some offsets within the cycle. Combine all of them with CRT to find
+    // the first step at which all ghosts are on a ..Z node.
+    let network = parse(input);
+    let ghosts = (0..network.names.len())
+        .filter(|n| network.names[*n].ends_with('A'))
+        .collect::<Vec<_>>();
+    if ghosts.is_empty() {
+        return None;
     }
-    periods
+    let walks = ghosts
+        .iter()
+        .map(|g| network.walk(*g, |n| n.ends_with('Z')));
+    intersect_all((0, 1), walks)
         .iter()
-        .fold(1_u64, |acc: u64, e| lcm(acc, *e as u64))
-        .into()
+        .map(|(first, _)| *first)
+        .min()
 }
 
 #[cfg(test)]
@@ -100,4 +118,37 @@ mod tests {
         ));
         assert_eq!(result, Some(6));
     }
+
+    /// A network walked with always the same instruction, from a path per
+    /// ghost such as "11A 11B 11Z 11B": each node leads to the next one.
+    fn network(paths: &[&str]) -> String {
+        let mut input = String::from("L\n\n");
+        for path in paths {
+            let nodes = path.split(' ').collect::<Vec<_>>();
+            for pair in nodes.windows(2) {
+                input += &format!("{} = ({}, {})\n", pair[0], pair[1], pair[1]);
+            }
+        }
+        input
+    }
+
+    #[test]
+    fn test_offsets() {
+        // Ends at steps 2, 4, 6... and 1, 4, 7...: not a multiple of periods
+        let input = network(&["11A 11B 11Z 11B", "22A 22Z 22B 22C 22Z"]);
+        assert_eq!(part_two(&input), Some(4));
+        // Several ends in a cycle: 2, 4, 6... and 1, 2, 4, 5, 7...
+        let input = network(&["11A 11B 11Z 11B", "44A 44Z 45Z 44B 44Z"]);
+        assert_eq!(part_two(&input), Some(2));
+        // An end before the cycle: 1 then 3, 5... and 1, 3, 5...
+        let input = network(&["55A 55Z 55B 55Z 55B", "66A 66Z 66B 66Z"]);
+        assert_eq!(part_two(&input), Some(1));
+        // Ends at even and odd steps only
+        let input = network(&["11A 11B 11Z 11B", "33A 33Z 33B 33Z"]);
+        assert_eq!(part_two(&input), None);
+        // Never reached
+        assert_eq!(part_one(&network(&["AAA BBB AAA", "ZZZ ZZZ"])), None);
+        // No ghosts at all
+        assert_eq!(part_two(&network(&["11B 11Z 11B"])), None);
+    }
 }
@@ -1,55 +1,38 @@
 advent_of_code::solution!(9);
 
-pub fn part_one(input: &str) -> Option<i64> {
-    let seqs = input
+use advent_of_code::parse::signed;
+use advent_of_code::sequence::Polynomial;
+
+fn parse(input: &str) -> Vec<Polynomial> {
+    input
         .lines()
-        .map(|line| {
-            line.split(' ')
-                .filter_map(|s| s.parse::<i64>().ok())
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    seqs.iter()
-        .map(|seq| {
-            let mut rows = vec![seq.clone()];
-            while rows[rows.len() - 1].iter().filter(|e| **e != 0).count() > 0 {
-                let row = (&rows[rows.len() - 1][..])
-                    .windows(2)
-                    .map(|w| w[1] - w[0])
-                    .collect::<Vec<_>>();
-                rows.push(row);
+        .enumerate()
+        .map(|(i, line)| {
+            let p = Polynomial::fit(&signed(line).collect::<Vec<_>>());
+            if !p.is_exact() {
+                eprintln!(
+                    "Warning: line {} never reaches zero differences, extrapolating degree {}",
+                    i + 1,
+                    p.degree()
+                );
             }
-            // Add up to the final value
-            (0..rows.len())
-                .map(|y| rows[y][rows[y].len() - 1])
-                .sum::<i64>()
+            p
         })
+        .collect()
+}
+
+pub fn part_one(input: &str) -> Option<i64> {
+    parse(input)
+        .iter()
+        .map(|p| p.forward(1))
         .sum::<i64>()
         .into()
 }
 
 pub fn part_two(input: &str) -> Option<i64> {
-    let seqs = input
-        .lines()
-        .map(|line| {
-            line.split(' ')
-                .filter_map(|s| s.parse::<i64>().ok())
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    seqs.iter()
-        .map(|seq| {
-            let mut rows = vec![seq.clone()];
-            while rows[rows.len() - 1].iter().filter(|e| **e != 0).count() > 0 {
-                let row = (&rows[rows.len() - 1][..])
-                    .windows(2)
-                    .map(|w| w[1] - w[0])
-                    .collect::<Vec<_>>();
-                rows.push(row);
-            }
-            // Add up to the final value
-            (0..rows.len()).rev().fold(0, |acc, i| rows[i][0] - acc)
-        })
+    parse(input)
+        .iter()
+        .map(|p| p.backward(1))
         .sum::<i64>()
         .into()
 }
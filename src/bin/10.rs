@@ -1,20 +1,13 @@
 advent_of_code::solution!(10);
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use advent_of_code::polygon::Polygon;
+use std::collections::HashMap;
 
-fn build_data(
-    input: &str,
-) -> (
-    HashMap<(isize, isize), char>,
-    HashSet<(isize, isize)>,
-    isize,
-    isize,
-    u32,
-) {
+type Tile = (isize, isize);
+
+fn parse(input: &str) -> (HashMap<Tile, char>, Tile) {
     let mut start = (0, 0);
     let mut field = HashMap::new();
-    let mut width = 0;
-    let mut height = 0;
     for (y, l) in input.lines().enumerate() {
         for (x, c) in l.chars().enumerate() {
             match c {
@@ -27,98 +20,62 @@ fn build_data(
                     field.insert((x as isize, y as isize), c);
                 }
             }
-            width = x as isize;
         }
-        height = y as isize;
-    }
-    width += 1;
-    height += 1;
-    // Initialize breath-first search of the loop
-    let mut open = VecDeque::new();
-    let mut visited = HashSet::new();
-    visited.insert(start);
-    match field.get(&(start.0, start.1 - 1)).unwrap_or(&'.') {
-        '|' | 'F' | '7' => open.push_back((start.0, start.1 - 1, 1)),
-        _ => {}
-    }
-    match field.get(&(start.0, start.1 + 1)).unwrap_or(&'.') {
-        '|' | 'L' | 'J' => open.push_back((start.0, start.1 + 1, 1)),
-        _ => {}
     }
-    match field.get(&(start.0 - 1, start.1)).unwrap_or(&'.') {
-        '-' | 'L' | 'F' => open.push_back((start.0 - 1, start.1, 1)),
-        _ => {}
-    }
-    match field.get(&(start.0 + 1, start.1)).unwrap_or(&'.') {
-        '-' | '7' | 'J' => open.push_back((start.0 + 1, start.1, 1)),
-        _ => {}
+    (field, start)
+}
+
+fn connections(pipe: char) -> [Tile; 2] {
+    match pipe {
+        '|' => [(0, -1), (0, 1)],
+        '-' => [(-1, 0), (1, 0)],
+        'L' => [(0, -1), (1, 0)],
+        'J' => [(0, -1), (-1, 0)],
+        '7' => [(0, 1), (-1, 0)],
+        'F' => [(0, 1), (1, 0)],
+        _ => [(0, 0), (0, 0)], // 'S' or ground: connects to nothing
     }
-    let mut distance = 0;
-    while open.len() > 0 {
-        let (x, y, steps) = open.pop_front().unwrap();
-        if visited.contains(&(x, y)) {
-            distance = steps;
-            break;
-        } else {
-            visited.insert((x, y));
-        }
-        let (front, back) = match field.get(&(x, y)).unwrap() {
-            '-' => ((x + 1, y), (x - 1, y)),
-            '7' => ((x - 1, y), (x, y + 1)),
-            'L' => ((x + 1, y), (x, y - 1)),
-            'J' => ((x - 1, y), (x, y - 1)),
-            'F' => ((x + 1, y), (x, y + 1)),
-            _ => ((x, y + 1), (x, y - 1)), // '|'
-        };
-        if !visited.contains(&front) {
-            open.push_back((front.0, front.1, steps + 1));
-        }
-        if !visited.contains(&back) {
-            open.push_back((back.0, back.1, steps + 1));
+}
+
+fn trace_loop(field: &HashMap<Tile, char>, start: Tile) -> Option<Vec<Tile>> {
+    // Leave start towards each pipe that connects back to it in turn, and
+    // follow pipes until we're back to start. Pipes next to start may also
+    // lead nowhere, or off the loop. Tiles are returned in loop order.
+    let step = |(x, y): Tile, (dx, dy): Tile| (x + dx, y + dy);
+    let walk = |first: Tile| {
+        let mut tiles = vec![start];
+        let (mut dir, mut curr) = (first, step(start, first));
+        while curr != start {
+            // The pipe must connect back to where we came from
+            let back = (-dir.0, -dir.1);
+            let pipes = connections(*field.get(&curr)?);
+            if !pipes.contains(&back) || tiles.len() > field.len() {
+                return None;
+            }
+            tiles.push(curr);
+            dir = pipes.into_iter().find(|d| *d != back)?;
+            curr = step(curr, dir);
         }
-    }
-    (field, visited, width, height, distance)
+        Some(tiles)
+    };
+    [(0, -1), (0, 1), (-1, 0), (1, 0)]
+        .into_iter()
+        .find_map(walk)
 }
 
 pub fn part_one(input: &str) -> Option<u32> {
-    Some(build_data(input).4)
+    let (field, start) = parse(input);
+    // The farthest tile is halfway around the loop
+    Some(trace_loop(&field, start)?.len() as u32 / 2)
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
-    let (field, visited, width, height, _) = build_data(input);
-    let mut enclosed = 0;
-    for x in 0..width {
-        let mut inside = false;
-        let mut entry = '.';
-        for y in 0..height {
-            if visited.contains(&(x, y)) {
-                match field[&(x, y)] {
-                    '-' => {
-                        inside = !inside;
-                    }
-                    'F' | '7' => {
-                        entry = field[&(x, y)];
-                    }
-                    'J' => {
-                        if entry == 'F' {
-                            inside = !inside;
-                        }
-                    }
-                    'L' => {
-                        if entry == '7' {
-                            inside = !inside;
-                        }
-                    }
-                    _ => {}
-                }
-            } else {
-                if inside {
-                    enclosed += 1;
-                }
-            }
-        }
-    }
-    Some(enclosed)
+    // The loop's tiles are lattice points on the boundary of a polygon; the
+    // enclosed tiles are the lattice points strictly inside it.
+    let (field, start) = parse(input);
+    let tiles = trace_loop(&field, start)?;
+    let enclosure = Polygon::from_vertices(tiles.iter().map(|(x, y)| (*x as i64, *y as i64)));
+    Some(enclosure.interior_points() as u32)
 }
 
 #[cfg(test)]
@@ -136,4 +93,15 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(1));
     }
+
+    #[test]
+    fn test_stray_pipes() {
+        // Pipes above and left of start point at it, but are not on the loop
+        let input = ".|...\n-S-7.\n.|.|.\n.L-J.\n.....\n";
+        assert_eq!(part_one(input), Some(4));
+        assert_eq!(part_two(input), Some(1));
+        // A pipe leading into the ground, and no loop at all
+        let input = "..F..\n-S-7.\n.|...\n";
+        assert_eq!(part_one(input), None);
+    }
 }
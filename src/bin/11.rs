@@ -1,60 +1,90 @@
 advent_of_code::solution!(11);
 
-use num::abs;
-use std::collections::HashMap;
+use advent_of_code::parse::paragraphs;
 
-pub fn distance(input: &str, expand: i64) -> Option<i64> {
-    let mut stars = HashMap::new();
-    let mut width = 0;
-    let mut height = 0;
-    // Do row expension and stars
-    let mut add = 0;
-    for (y, l) in input.lines().enumerate() {
-        let mut empty = true;
-        for (x, c) in l.chars().enumerate() {
-            match c {
-                '#' => {
-                    stars.insert((x, y), (0, add));
-                    empty = false;
+/// Coordinates of a galaxy, one per dimension.
+type Galaxy = Vec<i64>;
+
+/// Galaxies in reading order, as (x, y). Images separated by blank lines are
+/// layers of a 3D image, and galaxies are then (x, y, z).
+fn parse(input: &str) -> Vec<Galaxy> {
+    let layers = paragraphs(input).collect::<Vec<_>>();
+    let mut galaxies = Vec::new();
+    for (z, layer) in layers.iter().enumerate() {
+        for (y, l) in layer.lines().enumerate() {
+            for (x, _) in l.chars().enumerate().filter(|(_, c)| *c == '#') {
+                let mut galaxy = vec![x as i64, y as i64];
+                if layers.len() > 1 {
+                    galaxy.push(z as i64);
                 }
-                _ => {}
+                galaxies.push(galaxy);
             }
-            width = x; // hackish, but ok
-        }
-        if empty {
-            add += 1;
         }
-        height = y;
     }
-    width += 1;
-    height += 1;
-    // Do column expension now
-    add = 0;
-    for x in 0..width {
-        let mut empty = true;
-        for y in 0..height {
-            if stars.contains_key(&(x, y)) {
-                stars.insert((x, y), (add, stars[&(x, y)].1));
-                empty = false;
-            }
-        }
-        if empty {
-            add += 1;
+    galaxies
+}
+
+/// Galaxies after each line without galaxies, along each axis, has grown to
+/// `factor` lines.
+fn expand(galaxies: &[Galaxy], factor: i64) -> Vec<Galaxy> {
+    let mut expanded = galaxies.to_vec();
+    let dimensions = galaxies.first().map_or(0, |g| g.len());
+    for axis in 0..dimensions {
+        let mut occupied = galaxies.iter().map(|g| g[axis]).collect::<Vec<_>>();
+        occupied.sort_unstable();
+        occupied.dedup();
+        for galaxy in expanded.iter_mut() {
+            // Empty lines before this one, with the first line at 0
+            let c = galaxy[axis];
+            let empty = c - occupied.partition_point(|o| *o < c) as i64;
+            galaxy[axis] = c + (factor - 1) * empty;
         }
     }
-    // Compute distance for each pairs
-    let mut star_vec = stars.keys().collect::<Vec<_>>();
-    let mut dist = 0;
-    while star_vec.len() > 0 {
-        let star = star_vec.pop().unwrap();
-        for o in &star_vec {
-            let (i, j) = stars[&star];
-            let (k, l) = stars[&o];
-            dist += abs((star.0 as i64 + (expand - 1) * i) - (o.0 as i64 + (expand - 1) * k))
-                + abs((star.1 as i64 + (expand - 1) * j) - (o.1 as i64 + (expand - 1) * l));
+    expanded
+}
+
+/// Sum of distances between all pairs of sorted coordinates: each coordinate
+/// is the larger one in a pair with each of those before it.
+fn axis_distance(sorted: &[i64]) -> i64 {
+    let mut prefix = 0;
+    let mut sum = 0;
+    for (i, c) in sorted.iter().enumerate() {
+        sum += c * i as i64 - prefix;
+        prefix += c;
+    }
+    sum
+}
+
+/// Sum of Manhattan distances between all pairs of galaxies.
+fn total_distance(galaxies: &[Galaxy]) -> i64 {
+    // Manhattan distances are sums of distances along each axis, and those
+    // can be summed over all pairs at once in O(n log n).
+    let dimensions = galaxies.first().map_or(0, |g| g.len());
+    (0..dimensions)
+        .map(|axis| {
+            let mut coords = galaxies.iter().map(|g| g[axis]).collect::<Vec<_>>();
+            coords.sort_unstable();
+            axis_distance(&coords)
+        })
+        .sum()
+}
+
+/// Manhattan distance between each pair of galaxies (i, j), with i < j.
+#[allow(dead_code)]
+fn pair_distances(galaxies: &[Galaxy]) -> Vec<((usize, usize), i64)> {
+    let mut pairs = Vec::new();
+    for (i, a) in galaxies.iter().enumerate() {
+        for (j, b) in galaxies.iter().enumerate().skip(i + 1) {
+            let d = a.iter().zip(b).map(|(a, b)| (a - b).abs()).sum();
+            pairs.push(((i, j), d));
         }
     }
-    Some(dist)
+    pairs
+}
+
+pub fn distance(input: &str, expand_by: i64) -> Option<i64> {
+    let galaxies = expand(&parse(input), expand_by);
+    Some(total_distance(&galaxies))
 }
 
 pub fn part_one(input: &str) -> Option<i64> {
@@ -79,4 +109,36 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(82000210));
     }
+
+    #[test]
+    fn test_distance() {
+        let input = advent_of_code::template::read_file("examples", DAY);
+        assert_eq!(distance(&input, 10), Some(1030));
+        assert_eq!(distance(&input, 100), Some(8410));
+        let galaxies = expand(&parse(&input), 2);
+        let pairs = pair_distances(&galaxies);
+        assert_eq!(pairs.len(), 36);
+        for (pair, d) in [((4, 8), 9), ((0, 6), 15), ((2, 5), 17), ((7, 8), 5)] {
+            assert!(pairs.contains(&(pair, d)), "{pair:?}");
+        }
+        assert_eq!(pairs.iter().map(|(_, d)| d).sum::<i64>(), 374);
+    }
+
+    #[test]
+    fn test_layers() {
+        let input = "#..\n...\n..#\n\n...\n...\n...\n\n.#.\n...\n#..\n";
+        let galaxies = parse(input);
+        assert_eq!(
+            galaxies,
+            [vec![0, 0, 0], vec![2, 2, 0], vec![1, 0, 2], vec![0, 2, 2]]
+        );
+        // Row 1 and layer 1 are empty
+        let expanded = expand(&galaxies, 3);
+        assert_eq!(expanded[3], [0, 4, 4]);
+        let pairs = pair_distances(&expanded);
+        assert_eq!(
+            total_distance(&expanded),
+            pairs.iter().map(|(_, d)| d).sum()
+        );
+    }
 }
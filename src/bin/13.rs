@@ -1,182 +1,132 @@
 advent_of_code::solution!(13);
 
-use std::cmp::min;
-use std::iter::zip;
-
-type Pattern = Vec<Vec<bool>>;
-type Patterns = Vec<Pattern>;
-
-fn parse_patterns(input: &str) -> Patterns {
-    let mut patterns = Patterns::new();
-    let mut new_pattern = true;
-    for l in input.lines() {
-        if l.is_empty() {
-            new_pattern = true;
-        } else {
-            if new_pattern {
-                new_pattern = false;
-                patterns.push(Pattern::new());
-            }
-            patterns
-                .last_mut()
-                .unwrap()
-                .push(l.chars().map(|c| (c == '#')).collect::<Vec<_>>());
+use advent_of_code::parse::paragraphs;
+
+/// A pattern as bitmasks, both by row and by column: bit `x` of `rows[y]` and
+/// bit `y` of `columns[x]` are set for a rock at (x, y).
+struct Pattern {
+    rows: Vec<u64>,
+    columns: Vec<u64>,
+}
+
+fn parse_pattern(input: &str) -> Pattern {
+    let lines = input.lines().collect::<Vec<_>>();
+    assert!(
+        lines.len() <= 64 && lines[0].len() <= 64,
+        "pattern too large"
+    );
+    let mut rows = vec![0; lines.len()];
+    let mut columns = vec![0; lines[0].len()];
+    for (y, l) in lines.iter().enumerate() {
+        for (x, _) in l.chars().enumerate().filter(|(_, c)| *c == '#') {
+            rows[y] |= 1 << x;
+            columns[x] |= 1 << y;
         }
     }
-    patterns
+    Pattern { rows, columns }
+}
+
+fn parse_patterns(input: &str) -> Vec<Pattern> {
+    paragraphs(input).map(parse_pattern).collect()
 }
 
 #[allow(dead_code)]
 fn print_pattern(p: &Pattern) {
-    for l in p.iter() {
+    for row in p.rows.iter() {
         println!(
             "{}",
-            l.iter()
-                .map(|b| b.then(|| '#').unwrap_or('.'))
+            (0..p.columns.len())
+                .map(|x| if row >> x & 1 == 1 { '#' } else { '.' })
                 .collect::<String>()
         );
     }
 }
 
-fn find_horiz_reflections(pattern: &Pattern) -> Vec<usize> {
-    let mid_iter = pattern
-        .as_slice()
-        .windows(2)
-        .enumerate()
-        .filter_map(|(i, l)| (l[0] == l[1]).then(|| Some(i)));
-    let mut results = Vec::new();
-    for mid in mid_iter {
-        let mid = mid.unwrap() + 1;
-        let size = min(mid, pattern.len() - mid);
-        // Validate reflection
-        if pattern[mid - size..mid]
-            .iter()
-            .eq(pattern[mid..mid + size].iter().rev())
-        {
-            results.push(mid);
-        }
-    }
-    results
+#[derive(Debug, PartialEq)]
+struct Reflection {
+    /// Whether the axis is between rows, rather than between columns
+    horizontal: bool,
+    /// Number of rows above, or columns left of, the axis
+    position: usize,
+    /// Cells (x, y) to flip for the reflection to be exact, on the top or
+    /// left side of the axis (flipping their mirror cell works as well)
+    smudges: Vec<(usize, usize)>,
 }
 
-fn rotate(pattern: &Pattern) -> Pattern {
-    let mut vertical: Pattern = Pattern::new();
-    for row in 0..pattern.len() {
-        for col in 0..pattern[row].len() {
-            if vertical.len() <= col {
-                vertical.push(Vec::new());
-            }
-            vertical[col].push(pattern[row][col]);
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.horizontal {
+            true => 100 * self.position,
+            false => self.position,
         }
     }
-    vertical
-}
-
-fn one_reflection(pattern: &Pattern) -> Option<(bool, usize)> {
-    let result = find_horiz_reflections(pattern)
-        .get(0)
-        .and_then(|x| Some((true, *x)));
-    if result.is_some() {
-        return result;
-    }
-    // Rotate 90 degrees
-    let vertical = rotate(pattern);
-    find_horiz_reflections(&vertical)
-        .get(0)
-        .and_then(|x| Some((false, *x)))
-}
-
-fn reflections(patterns: &Patterns) -> Vec<Option<(bool, usize)>> {
-    let mut results: Vec<Option<(bool, usize)>> = vec![None; patterns.len()];
-    for (pattern, result) in zip(patterns.iter(), results.iter_mut()) {
-        //print_pattern(pattern);
-        *result = one_reflection(pattern);
-    }
-    results
 }
 
-fn desmudged_reflections(patterns: &Patterns) -> Vec<Option<(bool, usize)>> {
-    let mut results: Vec<Option<(bool, usize)>> = vec![None; patterns.len()];
-    for (pattern, result) in zip(patterns.iter(), results.iter_mut()) {
-        // Compute initial reflection first
-        let (horiz, mid) = one_reflection(pattern).unwrap();
-        // Rotate 90 degrees
-        let mut desmudged = pattern
-            .iter()
-            .map(|l| l.iter().map(|b| *b).collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-        //print_pattern(&desmudged);
-        let mut vertical = rotate(&desmudged);
-        // Brute force? Okay
-        for r in 0..desmudged.len() {
-            for c in 0..desmudged[r].len() {
-                // flip
-                desmudged[r][c] = !desmudged[r][c];
-                vertical[c][r] = !vertical[c][r];
-
-                let candidates = find_horiz_reflections(&desmudged);
-                for candidate in candidates {
-                    if !horiz || candidate != mid {
-                        // Must differ from initial reflection
-                        *result = Some((true, candidate));
-                        break;
-                    }
-                }
-
-                let candidates = find_horiz_reflections(&vertical);
-                for candidate in candidates {
-                    if horiz || candidate != mid {
-                        // Must differ from initial reflection
-                        *result = Some((false, candidate));
-                        break;
-                    }
-                }
-
-                // flip back - okay if not executed on break
-                desmudged[r][c] = !desmudged[r][c];
-                vertical[c][r] = !vertical[c][r];
+/// Axes between `lines` at which they are mirrored with exactly `smudges`
+/// differing bits, with those differences as (line, bit) pairs.
+fn axes(lines: &[u64], smudges: u32) -> Vec<(usize, Vec<(usize, usize)>)> {
+    let mut results = Vec::new();
+    'axis: for mid in 1..lines.len() {
+        let mut count = 0;
+        let mut diffs = Vec::new();
+        let pairs = (0..mid).rev().zip(mid..lines.len());
+        for (i, j) in pairs {
+            let mut diff = lines[i] ^ lines[j];
+            count += diff.count_ones();
+            if count > smudges {
+                continue 'axis;
             }
-            if result.is_some() {
-                break;
+            while diff != 0 {
+                diffs.push((i, diff.trailing_zeros() as usize));
+                diff &= diff - 1;
             }
         }
+        if count == smudges {
+            results.push((mid, diffs));
+        }
     }
     results
 }
 
-pub fn part_one(input: &str) -> Option<usize> {
-    // Identify lines repeated line (the middle of the reflection), go towards
-    // the edge to check.
-    let patterns: Patterns = parse_patterns(input);
-    let results = reflections(&patterns);
+impl Pattern {
+    /// All reflections with exactly `smudges` cells to flip, horizontal ones
+    /// first.
+    fn reflections(&self, smudges: u32) -> Vec<Reflection> {
+        let horizontal = axes(&self.rows, smudges)
+            .into_iter()
+            .map(|(position, diffs)| Reflection {
+                horizontal: true,
+                position,
+                smudges: diffs.into_iter().map(|(y, x)| (x, y)).collect(),
+            });
+        let vertical = axes(&self.columns, smudges)
+            .into_iter()
+            .map(|(position, diffs)| Reflection {
+                horizontal: false,
+                position,
+                smudges: diffs,
+            });
+        horizontal.chain(vertical).collect()
+    }
+}
 
-    // println!("{:?}", results);
-    results
+fn summarize(input: &str, smudges: u32) -> Option<usize> {
+    parse_patterns(input)
         .iter()
-        .map(|x| {
-            let (h, v) = x.unwrap();
-            v * h.then(|| 100).unwrap_or(1)
-        })
-        .sum::<usize>()
-        .into()
+        .map(|p| p.reflections(smudges).first().map(Reflection::summary))
+        .sum()
 }
 
-pub fn part_two(input: &str) -> Option<usize> {
-    let patterns: Patterns = parse_patterns(input);
-
-    // Brute-force? Flip each bit and test in both directions?
-    // Optimize with bit-wise operations later if needed
-    let results = desmudged_reflections(&patterns);
+pub fn part_one(input: &str) -> Option<usize> {
+    // Mismatches between mirrored rows are counted with a popcount of their
+    // xor, an exact reflection has none.
+    summarize(input, 0)
+}
 
-    // println!("{:?}", results);
-    results
-        .iter()
-        .map(|x| {
-            let (h, v) = x.unwrap();
-            v * h.then(|| 100).unwrap_or(1)
-        })
-        .sum::<usize>()
-        .into()
+pub fn part_two(input: &str) -> Option<usize> {
+    // The smudged reflection is the one with a single mismatch, which cannot
+    // be the exact one.
+    summarize(input, 1)
 }
 
 #[cfg(test)]
@@ -194,4 +144,28 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(400));
     }
+
+    #[test]
+    fn test_reflections() {
+        let patterns = parse_patterns(&advent_of_code::template::read_file("examples", DAY));
+        let reflection = |horizontal, position, smudges| Reflection {
+            horizontal,
+            position,
+            smudges,
+        };
+        assert_eq!(patterns[0].reflections(0), [reflection(false, 5, vec![])]);
+        assert_eq!(
+            patterns[0].reflections(1),
+            [reflection(true, 3, vec![(0, 0)])]
+        );
+        assert_eq!(patterns[1].reflections(0), [reflection(true, 4, vec![])]);
+        assert_eq!(
+            patterns[1].reflections(1),
+            [reflection(true, 1, vec![(4, 0)])]
+        );
+        // Two smudges: several axes, each one with a smudge per difference
+        let twice = patterns[1].reflections(2);
+        assert!(!twice.is_empty());
+        assert!(twice.iter().all(|r| r.smudges.len() == 2));
+    }
 }
@@ -2,13 +2,7 @@ advent_of_code::solution!(14);
 
 use std::collections::HashMap;
 
-#[derive(PartialEq, Copy, Clone)]
-enum Rock {
-    Round,
-    Square,
-}
-
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone)]
 enum Direction {
     North,
     East,
@@ -16,146 +10,207 @@ enum Direction {
     West,
 }
 
-type Field = Vec<Vec<Option<Rock>>>;
-
-fn parse(input: &str) -> Field {
-    input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| {
-            l.chars()
-                .map(|c| match c {
-                    'O' => Some(Rock::Round),
-                    '#' => Some(Rock::Square),
-                    _ => None,
-                })
-                .collect::<Vec<_>>()
+/// A program of tilts, such as "NWSE".
+fn program(tilts: &str) -> Vec<Direction> {
+    tilts
+        .chars()
+        .map(|c| match c {
+            'N' => Direction::North,
+            'E' => Direction::East,
+            'S' => Direction::South,
+            'W' => Direction::West,
+            _ => panic!("{c} is not a direction"),
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
-fn step_direction(field: &mut Field, direction: Direction) -> bool {
-    let mut moved = false;
-    for y in 0..field.len() {
-        for x in 0..field[y].len() {
-            if let Some(rock) = field[y][x] {
-                if rock == Rock::Square {
-                    continue;
-                }
-                if direction == Direction::North && y == 0
-                    || direction == Direction::West && x == 0
-                    || direction == Direction::South && y == field.len() - 1
-                    || direction == Direction::East && x == field[y].len() - 1
-                {
-                    continue;
-                }
-                let (i, j) = match direction {
-                    Direction::North => (x + 0, y - 1),
-                    Direction::East => (x + 1, y + 0),
-                    Direction::South => (x + 0, y + 1),
-                    Direction::West => (x - 1, y + 0),
-                };
-                if field[j][i].is_some() {
-                    continue;
-                }
-                // Move rock to the top
-                field[j][i] = Some(rock);
-                field[y][x] = None;
-                moved = true;
-            }
-        }
-    }
-    moved
+/// Rocks as bitsets: bit `x` of `round[y]` is set if there is a round rock at
+/// column `x` of row `y`. Cube rocks never move, so they are only kept as
+/// the runs of free cells between them, along rows and along columns. Round
+/// rocks are stored along rows, or along columns (transposed) after tilting
+/// north or south.
+#[derive(Debug, Clone)]
+struct Platform {
+    width: usize,
+    height: usize,
+    round: Vec<u128>,
+    transposed: bool,
+    row_runs: Vec<Vec<u128>>,
+    column_runs: Vec<Vec<u128>>,
 }
 
-fn tilt(field: &mut Field, direction: Direction) {
-    while step_direction(field, direction) {}
+/// The lowest `n` bits set.
+fn ones(n: u32) -> u128 {
+    u128::MAX.checked_shr(128 - n).unwrap_or(0)
 }
 
-fn cycle(field: &mut Field) {
-    tilt(field, Direction::North);
-    tilt(field, Direction::West);
-    tilt(field, Direction::South);
-    tilt(field, Direction::East);
-}
-
-fn weight_north(field: &Field) -> usize {
-    field
-        .iter()
-        .rev() // start from south
-        .enumerate()
-        .map(|(w, l)| {
-            l.iter()
-                .filter_map(|r| match r {
-                    Some(Rock::Round) => Some(w + 1),
-                    _ => None,
-                })
-                .sum::<usize>()
+fn transpose(lines: &[u128], len: usize) -> Vec<u128> {
+    (0..len)
+        .map(|i| {
+            lines
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (j, line)| acc | (line >> i & 1) << j)
         })
-        .sum::<usize>()
+        .collect()
 }
 
-#[allow(dead_code)]
-fn print_field(field: &Field) {
-    for l in field.iter() {
-        println!(
-            "{}",
-            l.iter()
-                .map(|r| match r {
-                    Some(Rock::Round) => 'O',
-                    Some(Rock::Square) => '#',
-                    _ => '.',
-                })
-                .collect::<String>()
-        );
+/// Maximal runs of bits not set in `cubes`, among the lowest `len` bits.
+fn runs(cubes: u128, len: usize) -> Vec<u128> {
+    let mut runs = vec![];
+    let mut free = !cubes & ones(len as u32);
+    while free != 0 {
+        let start = free.trailing_zeros();
+        let len = (free >> start).trailing_ones();
+        let run = ones(len) << start;
+        runs.push(run);
+        free &= !run;
     }
+    runs
 }
 
-fn field_key(field: &Field) -> Vec<bool> {
-    field
-        .iter()
-        .map(|l| {
-            l.iter().map(|r| match r {
-                Some(Rock::Round) => true,
-                _ => false,
+fn parse(input: &str) -> Platform {
+    let lines = input.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+    let (width, height) = (lines[0].len(), lines.len());
+    assert!(width <= 128 && height <= 128, "platform too large");
+    let bits = |rock: char| {
+        lines
+            .iter()
+            .map(|l| {
+                l.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == rock)
+                    .fold(0, |acc, (x, _)| acc | 1 << x)
             })
-        })
-        .flatten()
-        .collect::<Vec<_>>()
+            .collect::<Vec<u128>>()
+    };
+    let cubes = bits('#');
+    Platform {
+        width,
+        height,
+        round: bits('O'),
+        transposed: false,
+        row_runs: cubes.iter().map(|c| runs(*c, width)).collect(),
+        column_runs: transpose(&cubes, width)
+            .iter()
+            .map(|c| runs(*c, height))
+            .collect(),
+    }
 }
 
-fn cycle_until(field: &mut Field, n: usize) {
-    let mut seen = HashMap::new();
-    let mut count = 0;
-    while count < n {
-        let key = field_key(&field);
-        if let Some(prev_count) = seen.get(&key) {
-            let period = count - prev_count;
-            let remaining = (n - count) % period;
-            for _ in 0..remaining {
-                cycle(field);
+impl Platform {
+    /// Round rocks along rows.
+    fn rows(&self) -> Vec<u128> {
+        match self.transposed {
+            true => transpose(&self.round, self.height),
+            false => self.round.clone(),
+        }
+    }
+
+    fn orient(&mut self, transposed: bool) {
+        if self.transposed != transposed {
+            let len = if transposed { self.width } else { self.height };
+            self.round = transpose(&self.round, len);
+            self.transposed = transposed;
+        }
+    }
+
+    /// Tilts the platform in one pass: round rocks in each run of free cells
+    /// gather at the run's low or high end.
+    fn tilt(&mut self, direction: Direction) {
+        let vertical = matches!(direction, Direction::North | Direction::South);
+        let low = matches!(direction, Direction::North | Direction::West);
+        self.orient(vertical);
+        let all_runs = if vertical {
+            &self.column_runs
+        } else {
+            &self.row_runs
+        };
+        for (line, runs) in self.round.iter_mut().zip(all_runs) {
+            let mut tilted = 0;
+            for run in runs {
+                let count = (*line & run).count_ones();
+                tilted |= match low {
+                    true => ones(count) << run.trailing_zeros(),
+                    false => ones(count) << (128 - run.leading_zeros() - count),
+                };
             }
-            return;
+            *line = tilted;
         }
-        seen.insert(key, count);
-        cycle(field);
-        count += 1;
+    }
+
+    /// Runs `program` `times` times. The platform ends up in a cycle, which
+    /// is detected to skip repeated runs.
+    fn run(&mut self, program: &[Direction], times: usize) {
+        let mut seen = HashMap::new();
+        let mut count = 0;
+        while count < times {
+            if let Some(prev_count) = seen.insert(self.rows(), count) {
+                let remaining = (times - count) % (count - prev_count);
+                for _ in 0..remaining {
+                    program.iter().for_each(|d| self.tilt(*d));
+                }
+                return;
+            }
+            program.iter().for_each(|d| self.tilt(*d));
+            count += 1;
+        }
+    }
+
+    /// Total load of round rocks on the side of the platform at `side`.
+    fn load(&self, side: Direction) -> usize {
+        let rows = self.rows();
+        let rocks = rows.iter().enumerate().flat_map(|(y, row)| {
+            (0..self.width)
+                .filter(move |x| row >> x & 1 == 1)
+                .map(move |x| (x, y))
+        });
+        rocks
+            .map(|(x, y)| match side {
+                Direction::North => self.height - y,
+                Direction::South => y + 1,
+                Direction::West => self.width - x,
+                Direction::East => x + 1,
+            })
+            .sum()
+    }
+}
+
+#[allow(dead_code)]
+fn print_field(platform: &Platform) {
+    let rows = platform.rows();
+    let cubes = transpose(
+        &platform
+            .column_runs
+            .iter()
+            .map(|runs| !runs.iter().fold(0, |acc, r| acc | r))
+            .collect::<Vec<_>>(),
+        platform.height,
+    );
+    for y in 0..platform.height {
+        let line = (0..platform.width)
+            .map(|x| match (rows[y] >> x & 1, cubes[y] >> x & 1) {
+                (1, _) => 'O',
+                (_, 1) => '#',
+                _ => '.',
+            })
+            .collect::<String>();
+        println!("{line}");
     }
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
-    let mut field = parse(input);
-    tilt(&mut field, Direction::North);
-    // print_field(&field);
-    weight_north(&field).into()
+    let mut platform = parse(input);
+    platform.tilt(Direction::North);
+    // print_field(&platform);
+    platform.load(Direction::North).into()
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
-    let mut field = parse(input);
-    // Find the period and extrapolate...
-    cycle_until(&mut field, 1000000000);
-    // print_field(&field);
-    weight_north(&field).into()
+    let mut platform = parse(input);
+    platform.run(&program("NWSE"), 1000000000);
+    // print_field(&platform);
+    platform.load(Direction::North).into()
 }
 
 #[cfg(test)]
@@ -173,4 +228,30 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(64));
     }
+
+    #[test]
+    fn test_program() {
+        let mut platform = parse(&advent_of_code::template::read_file("examples", DAY));
+        let initial = platform.rows();
+        platform.run(&program("NWSE"), 3);
+        assert_eq!(platform.load(Direction::North), 69);
+        // Going back and forth ends up the same way
+        let mut platform = parse(&advent_of_code::template::read_file("examples", DAY));
+        platform.run(&program("EW"), 1);
+        let once = platform.rows();
+        platform.run(&program("EWEW"), 5);
+        assert_eq!(platform.rows(), once);
+        assert_ne!(platform.rows(), initial);
+        // Loads on each side of a single rock
+        let mut platform = parse(".O.\n...\n#..");
+        platform.run(&program("SE"), 1);
+        let loads = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .map(|side| platform.load(side));
+        assert_eq!(loads, [1, 3, 3, 1]);
+    }
 }
@@ -1,7 +1,6 @@
 advent_of_code::solution!(16);
 
-use std::cmp::max;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 
 type Field = HashMap<(isize, isize), char>;
 type Beam = ((isize, isize), (isize, isize));
@@ -20,8 +19,8 @@ fn parse(input: &str) -> (Field, usize, usize) {
             })
             .flatten()
             .collect::<Field>(),
-        input.lines().count(),
         input.lines().next().unwrap().len(),
+        input.lines().filter(|l| !l.is_empty()).count(),
     )
 }
 
@@ -34,67 +33,218 @@ fn dt(((x, y), (dx, dy)): Beam) -> Beam {
     ((x + dx, y + dy), (dx, dy))
 }
 
-fn propagate(beam: &Beam, field: &Field, explore: &mut VecDeque<Beam>) {
-    match field.get(&beam.0) {
-        Some('/') => match beam.1 {
-            NORTH => explore.push_back(dt((beam.0, EAST))),
-            EAST => explore.push_back(dt((beam.0, NORTH))),
-            SOUTH => explore.push_back(dt((beam.0, WEST))),
-            _ => explore.push_back(dt((beam.0, SOUTH))),
-        },
-        Some('\\') => match beam.1 {
-            NORTH => explore.push_back(dt((beam.0, WEST))),
-            EAST => explore.push_back(dt((beam.0, SOUTH))),
-            SOUTH => explore.push_back(dt((beam.0, EAST))),
-            _ => explore.push_back(dt((beam.0, NORTH))),
-        },
-        Some('|') => match beam.1 {
-            EAST | WEST => {
-                explore.push_back(dt((beam.0, NORTH)));
-                explore.push_back(dt((beam.0, SOUTH)));
+/// Tiles as bits, in a grid of `width` by `height` tiles.
+#[derive(Debug, Clone, PartialEq)]
+struct Tiles(Vec<u64>);
+
+impl Tiles {
+    fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn union(&mut self, other: &Tiles) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a |= b;
+        }
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.0[i / 64] & 1 << (i % 64) != 0
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+}
+
+/// The contraption compiled into a graph of beam segments. Splitters hit on
+/// their flat side are the vertices: whichever side they're hit from, they
+/// send beams the same two ways, which go through mirrors and pointy ends of
+/// splitters until they leave the grid or hit a splitter's flat side. The
+/// tiles energized from a splitter are then the ones on its segments and
+/// the segments of all splitters reachable from it, which are computed once
+/// for all.
+struct Contraption {
+    field: Field,
+    width: isize,
+    height: isize,
+    splitters: HashMap<(isize, isize), usize>,
+    // Tiles energized by the beams coming out of each splitter
+    energized: Vec<Tiles>,
+}
+
+impl Contraption {
+    fn new(field: Field, width: usize, height: usize) -> Self {
+        let mut splitters = field
+            .iter()
+            .filter(|(_, c)| **c == '|' || **c == '-')
+            .map(|(p, _)| *p)
+            .collect::<Vec<_>>();
+        splitters.sort();
+        let mut contraption = Self {
+            field,
+            width: width as isize,
+            height: height as isize,
+            splitters: splitters.iter().enumerate().map(|(i, p)| (*p, i)).collect(),
+            energized: vec![],
+        };
+        // Segments out of each splitter: tiles, and splitters hit
+        let mut tiles = vec![];
+        let mut next = vec![];
+        for pos in splitters.iter() {
+            let mut own = Tiles::new(width * height);
+            let mut hits = vec![];
+            for dir in contraption.outputs(*pos) {
+                let (segment, hit) = contraption.trace(dt((*pos, dir)));
+                own.insert(contraption.index(*pos));
+                own.union(&segment);
+                hits.extend(hit);
+            }
+            tiles.push(own);
+            next.push(hits);
+        }
+        contraption.energized = collapse(&tiles, &next);
+        contraption
+    }
+
+    fn index(&self, (x, y): (isize, isize)) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// Directions a splitter sends beams, when hit on its flat side.
+    fn outputs(&self, pos: (isize, isize)) -> [(isize, isize); 2] {
+        match self.field[&pos] {
+            '|' => [NORTH, SOUTH],
+            _ => [EAST, WEST],
+        }
+    }
+
+    /// Follows a beam until it leaves the grid, loops, or hits a splitter's
+    /// flat side. Returns the tiles it went through, and the splitter hit.
+    fn trace(&self, mut beam: Beam) -> (Tiles, Option<usize>) {
+        let mut tiles = Tiles::new((self.width * self.height) as usize);
+        let mut seen = HashSet::new();
+        while let Some(c) = self.field.get(&beam.0) {
+            if !seen.insert(beam) {
+                break; // Going around in circles
             }
-            _ => {
-                explore.push_back(dt(*beam));
+            tiles.insert(self.index(beam.0));
+            let (pos, dir) = beam;
+            beam = match (c, dir) {
+                ('/', NORTH) | ('\\', SOUTH) => dt((pos, EAST)),
+                ('/', EAST) | ('\\', WEST) => dt((pos, NORTH)),
+                ('/', SOUTH) | ('\\', NORTH) => dt((pos, WEST)),
+                ('/', _) | ('\\', _) => dt((pos, SOUTH)),
+                ('|', EAST | WEST) | ('-', NORTH | SOUTH) => {
+                    return (tiles, Some(self.splitters[&pos]));
+                }
+                _ => dt(beam),
+            };
+        }
+        (tiles, None)
+    }
+
+    fn energized(&self, start: Beam) -> Tiles {
+        let (mut tiles, hit) = self.trace(start);
+        if let Some(splitter) = hit {
+            tiles.union(&self.energized[splitter]);
+        }
+        tiles
+    }
+}
+
+/// Tiles reachable from each vertex, given the tiles of each vertex and the
+/// vertices it leads to. Vertices in a cycle reach the same tiles, so they
+/// are merged into strongly connected components first (Tarjan), which come
+/// out in reverse topological order: the components a component leads to are
+/// done before it.
+fn collapse(tiles: &[Tiles], next: &[Vec<usize>]) -> Vec<Tiles> {
+    struct Search<'a> {
+        tiles: &'a [Tiles],
+        next: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        reach: Vec<Option<Tiles>>,
+    }
+
+    fn visit(s: &mut Search, v: usize, count: &mut usize) {
+        s.index[v] = Some(*count);
+        s.low[v] = *count;
+        *count += 1;
+        s.stack.push(v);
+        s.on_stack[v] = true;
+        for &w in s.next[v].iter() {
+            match s.index[w] {
+                None => {
+                    visit(s, w, count);
+                    s.low[v] = s.low[v].min(s.low[w]);
+                }
+                Some(index) if s.on_stack[w] => s.low[v] = s.low[v].min(index),
+                _ => {}
             }
-        },
-        Some('-') => match beam.1 {
-            NORTH | SOUTH => {
-                explore.push_back(dt((beam.0, EAST)));
-                explore.push_back(dt((beam.0, WEST)));
+        }
+        if Some(s.low[v]) != s.index[v] {
+            return;
+        }
+        let mut members = vec![];
+        while let Some(w) = s.stack.pop() {
+            s.on_stack[w] = false;
+            members.push(w);
+            if w == v {
+                break;
             }
-            _ => {
-                explore.push_back(dt(*beam));
+        }
+        // Components out of this one are done, the ones inside are not yet
+        let mut reach = s.tiles[v].clone();
+        for &w in members.iter() {
+            reach.union(&s.tiles[w]);
+            for &u in s.next[w].iter() {
+                if let Some(tiles) = &s.reach[u] {
+                    reach.union(tiles);
+                }
             }
-        },
-        Some('.') => explore.push_back(dt(*beam)),
-        _ => {}
+        }
+        for w in members {
+            s.reach[w] = Some(reach.clone());
+        }
     }
-}
 
-fn energized(field: &Field, start: Beam) -> HashSet<(isize, isize)> {
-    let mut beams = HashSet::new();
-    let mut explore = VecDeque::from([start]);
-    while !explore.is_empty() {
-        let beam = explore.pop_front().unwrap();
-        if beams.contains(&beam) || !field.contains_key(&beam.0) {
-            continue;
+    let n = tiles.len();
+    let mut search = Search {
+        tiles,
+        next,
+        index: vec![None; n],
+        low: vec![0; n],
+        stack: vec![],
+        on_stack: vec![false; n],
+        reach: vec![None; n],
+    };
+    let mut count = 0;
+    for v in 0..n {
+        if search.index[v].is_none() {
+            visit(&mut search, v, &mut count);
         }
-        beams.insert(beam);
-        propagate(&beam, field, &mut explore);
     }
-    beams.iter().map(|(p, _)| p.clone()).collect::<HashSet<_>>()
+    search.reach.into_iter().map(Option::unwrap).collect()
 }
 
 #[allow(dead_code)]
-fn print_energized(tiles: &HashSet<(isize, isize)>, width: usize, height: usize) {
+fn print_energized(tiles: &Tiles, width: usize, height: usize) {
     for y in 0..height {
         for x in 0..width {
             print!(
                 "{}",
-                tiles
-                    .contains(&(x as isize, y as isize))
-                    .then(|| '#')
-                    .unwrap_or('.')
+                if tiles.contains(y * width + x) {
+                    '#'
+                } else {
+                    '.'
+                }
             );
         }
         println!();
@@ -102,30 +252,27 @@ fn print_energized(tiles: &HashSet<(isize, isize)>, width: usize, height: usize)
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
-    let (field, _, _) = parse(input);
-    let tiles = energized(&field, ((0, 0), EAST));
+    let (field, width, height) = parse(input);
+    let contraption = Contraption::new(field, width, height);
+    let tiles = contraption.energized(((0, 0), EAST));
     //print_energized(&tiles, width, height);
     Some(tiles.len())
 }
 
-fn max_energized(field: &Field, width: usize, height: usize) -> usize {
-    let mut m = 0;
-    let width = width as isize;
-    let height = height as isize;
-    for y in 0..height {
-        m = max(m, energized(&field, ((0, y), EAST)).len());
-        m = max(m, energized(&field, ((width - 1, y), WEST)).len());
-    }
-    for x in 0..width {
-        m = max(m, energized(&field, ((x, 0), SOUTH)).len());
-        m = max(m, energized(&field, ((x, height - 1), NORTH)).len());
-    }
-    m
+fn max_energized(contraption: &Contraption) -> usize {
+    let (width, height) = (contraption.width, contraption.height);
+    let starts = (0..height)
+        .flat_map(|y| [((0, y), EAST), ((width - 1, y), WEST)])
+        .chain((0..width).flat_map(|x| [((x, 0), SOUTH), ((x, height - 1), NORTH)]));
+    starts
+        .map(|start| contraption.energized(start).len())
+        .max()
+        .unwrap_or(0)
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
     let (field, width, height) = parse(input);
-    max_energized(&field, width, height).into()
+    max_energized(&Contraption::new(field, width, height)).into()
 }
 
 #[cfg(test)]
@@ -143,4 +290,22 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(51));
     }
+
+    #[test]
+    fn test_energized() {
+        let (field, width, height) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let contraption = Contraption::new(field, width, height);
+        assert_eq!(contraption.energized(((3, 0), SOUTH)).len(), 51);
+        let tiles = contraption.energized(((0, 0), EAST));
+        assert!(tiles.contains(4 * width + 5) && !tiles.contains(4 * width + 7));
+        // Wider than tall
+        let input = "...........\\\n.........../\n............\n";
+        assert_eq!(part_one(input), Some(24));
+        let (field, width, height) = parse(input);
+        assert_eq!((width, height), (12, 3));
+        let contraption = Contraption::new(field, width, height);
+        let tiles = contraption.energized(((0, 1), EAST));
+        assert_eq!(tiles.len(), 24);
+        assert!(tiles.contains(width) && !tiles.contains(2 * width));
+    }
 }
@@ -1,7 +1,8 @@
 advent_of_code::solution!(17);
 
 use num::abs;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 
 type _2D = (isize, isize);
 
@@ -43,202 +44,156 @@ fn dist((ax, ay): _2D, (bx, by): _2D) -> u32 {
     (abs(ax - bx) + abs(ay - by)) as u32
 }
 
-fn start_moving(
+/// How many blocks a crucible moves in a straight line: at least `min_run`
+/// before it can turn or stop, and at most `max_run`.
+#[derive(Debug, Clone, Copy)]
+struct Crucible {
+    min_run: u32,
+    max_run: u32,
+}
+
+const CRUCIBLE: Crucible = Crucible {
+    min_run: 1,
+    max_run: 3,
+};
+
+const ULTRA_CRUCIBLE: Crucible = Crucible {
+    min_run: 4,
+    max_run: 10,
+};
+
+/// A crucible on a block, having moved `run` blocks towards `dir`.
+type State = (_2D, _2D, u32);
+
+/// The least heat loss from `start` to `target` and the blocks on the way, from
+/// `start` to `target`, or `None` if the crucible can't get there.
+fn least_heatloss(
     field: &Field,
+    crucible: Crucible,
     start: _2D,
     target: _2D,
-) -> Vec<(u32, _2D, _2D, u32, u32, Vec<_2D>)> {
-    [NORTH, SOUTH, EAST, WEST]
-        .iter()
-        .filter(|dir| field.heatmap.contains_key(&add(start, **dir)))
-        .map(|dir| {
-            let next = add(start, *dir);
-            (
-                dist(target, next),
-                next,
-                *dir,
-                field.heatmap[&next],
-                0_u32,
-                vec![],
-            )
-        })
-        .collect::<Vec<_>>()
-}
-
-#[allow(dead_code)]
-fn print_debug(field: &Field, path: &Vec<_2D>) {
-    for y in 0..field.height as isize {
-        for x in 0..field.width as isize {
-            if path.contains(&(x, y)) {
-                print!("#");
-            } else {
-                print!("{}", field.heatmap[&(x, y)]);
-            }
+) -> Option<(u32, Vec<_2D>)> {
+    // Implement A*. Heuristic (h) is current heatloss + taxicab distance
+    // times the least heat loss of any block, which never overestimates, and
+    // does not decrease by more than a step's heat loss; so the first time a
+    // state is taken out of the queue, it is with its least heat loss. With
+    // blocks losing 0 heat, this is Dijkstra. States remember the one they
+    // came from, to rebuild the path.
+    let min_heat = field.heatmap.values().min().copied().unwrap_or(0);
+    let runs = crucible.max_run as usize + 1;
+    let index = |((x, y), dir, run): State| {
+        let dir = [NORTH, SOUTH, EAST, WEST].iter().position(|d| *d == dir);
+        ((y as usize * field.width + x as usize) * 4 + dir.unwrap()) * runs + run as usize
+    };
+    let states = field.width * field.height * 4 * runs;
+    let mut least = vec![u32::MAX; states];
+    let mut from: Vec<Option<State>> = vec![None; states];
+    let mut done = vec![false; states];
+    let mut open = BinaryHeap::new();
+    let mut push = |open: &mut BinaryHeap<_>, prev: Option<State>, heat: u32, state: State| {
+        let (head, _, _) = state;
+        let new_heat = heat + field.heatmap[&head];
+        if new_heat < least[index(state)] {
+            least[index(state)] = new_heat;
+            from[index(state)] = prev;
+            open.push(Reverse((
+                new_heat + dist(head, target) * min_heat,
+                new_heat,
+                state,
+            )));
         }
-        println!();
-    }
-    println!();
-}
-
-fn least_heatloss(field: &Field, start: _2D, target: _2D) -> u32 {
-    // Implement A*.  Heurisitic (h) is current heatloss + taxicab distance.
-    // The idea is to make the algorithm first explore paths that are not
-    // deviating too much from the straight line.  This way we quickly get a
-    // better "best" which makes us prune more, etc.  Search stops when runing
-    // into more optimal path.
-    let mut open = start_moving(&field, start, target);
-    let mut best = u32::MAX;
-    let mut visited: HashMap<(_2D, _2D), [u32; 3]> = HashMap::new();
-    while !open.is_empty() {
-        let (_h, head, dir, heat, fwd, _path) = open.pop().unwrap();
-        if heat + dist(head, target) >= best {
-            continue; // Will never beat best
+    };
+    for dir in [NORTH, SOUTH, EAST, WEST] {
+        if field.heatmap.contains_key(&add(start, dir)) {
+            push(&mut open, None, 0, (add(start, dir), dir, 1));
         }
-        if head == target {
-            if heat < best {
-                best = heat;
-                //print_debug(&field, &_path);
-            }
-            continue;
+    }
+    while let Some(Reverse((_h, heat, state))) = open.pop() {
+        if done[index(state)] {
+            continue; // Already reached with less heat loss
         }
-
-        if visited.contains_key(&(head, dir)) {
-            let val = visited[&(head, dir)];
-            // States with different fwd values should coexist. You can compare
-            // a low fwd only with a low fwd, but a high fwd should be
-            // comparable to any of the lower fwd best value.
-            //
-            // There's probably more optimisation: it can be observed that 2
-            // adjacent dimentions cover all possible outgoing directions. Thus
-            // it's probably not necessary to store separate states for each
-            // directions. I couldn't really figure out the proper way to
-            // express this.
-            if val[0..=(fwd as usize)].iter().min().unwrap() <= &heat {
-                continue; // Will never beat best
-            } else {
-                // improve value
-                let mut val = val;
-                val[fwd as usize] = heat;
-                visited.insert((head, dir), val);
+        done[index(state)] = true;
+        let (head, dir, run) = state;
+        if head == target && run >= crucible.min_run {
+            let mut path = vec![head];
+            let mut state = state;
+            while let Some(prev) = from[index(state)] {
+                path.push(prev.0);
+                state = prev;
             }
-        } else {
-            let mut val = [u32::MAX; 3];
-            val[fwd as usize] = heat;
-            visited.insert((head, dir), val);
+            path.push(start);
+            path.reverse();
+            return Some((heat, path));
         }
-
         for new_dir in [NORTH, SOUTH, EAST, WEST] {
             if new_dir == (-dir.0, -dir.1) {
                 continue; // don't go backward
             }
-            let mut new_fwd = 0;
-            if dir == new_dir {
-                if fwd == 2 {
-                    continue; // don't go forward
-                } else {
-                    new_fwd = fwd + 1;
-                }
+            let new_run = if new_dir == dir { run + 1 } else { 1 };
+            if new_run > crucible.max_run || new_dir != dir && run < crucible.min_run {
+                continue; // must turn, or can't turn yet
             }
-            let new_head = (head.0 + new_dir.0, head.1 + new_dir.1);
+            let new_head = add(head, new_dir);
             if !field.heatmap.contains_key(&new_head) {
                 continue; // don't go out
             }
-            let new_heat = heat + field.heatmap[&new_head];
-            let new_h = new_heat + dist(new_head, target);
-            let pos = open.partition_point(|a| new_h < a.0);
-            let mut new_path = _path.clone();
-            new_path.push(head);
-            open.insert(pos, (new_h, new_head, new_dir, new_heat, new_fwd, new_path));
+            push(&mut open, Some(state), heat, (new_head, new_dir, new_run));
         }
     }
-    best
+    None
 }
 
-fn least_heatloss_ultra(field: &Field, start: _2D, target: _2D) -> u32 {
-    let mut open = start_moving(&field, start, target);
-    let mut best = u32::MAX;
-    let mut visited: HashMap<(_2D, _2D), [u32; 10]> = HashMap::new();
-    while !open.is_empty() {
-        let (_h, head, dir, heat, fwd, _path) = open.pop().unwrap();
-        if heat + dist(head, target) >= best {
-            continue; // Will never beat best
-        }
-        if head == target {
-            if heat < best && fwd >= 3 {
-                best = heat;
-                //print_debug(&field, &_path);
-            }
-            continue;
-        }
-
-        if visited.contains_key(&(head, dir)) {
-            let val = visited[&(head, dir)];
-            // An ultra crucible cannot compare values below fwd=3 the same way
-            if fwd >= 3 && val[3..=(fwd as usize)].iter().min().unwrap() <= &heat
-                || val[fwd as usize] <= heat
-            {
-                continue; // Will never beat best
-            } else {
-                // improve value
-                let mut val = val;
-                val[fwd as usize] = heat;
-                visited.insert((head, dir), val);
-            }
-        } else {
-            let mut val = [u32::MAX; 10];
-            val[fwd as usize] = heat;
-            visited.insert((head, dir), val);
-        }
-
-        for new_dir in [NORTH, SOUTH, EAST, WEST] {
-            if new_dir == (-dir.0, -dir.1) {
-                continue; // don't go backward
-            }
-            let mut new_fwd = 0;
-            if dir == new_dir {
-                if fwd == 9 {
-                    continue; // don't go forward
-                } else {
-                    new_fwd = fwd + 1;
-                }
-            } else {
-                if fwd < 3 {
-                    continue; // can't turn
-                }
-            }
-            let new_head = (head.0 + new_dir.0, head.1 + new_dir.1);
-            if !field.heatmap.contains_key(&new_head) {
-                continue; // don't go away
+/// The field with the path drawn over it, as in the puzzle's description.
+fn render_path(field: &Field, path: &[_2D]) -> String {
+    let mut arrows = HashMap::new();
+    for (a, b) in path.iter().zip(path.iter().skip(1)) {
+        let arrow = match (b.0 - a.0, b.1 - a.1) {
+            NORTH => '^',
+            SOUTH => 'v',
+            WEST => '<',
+            _ => '>',
+        };
+        arrows.insert(*b, arrow);
+    }
+    let mut grid = String::new();
+    for y in 0..field.height as isize {
+        for x in 0..field.width as isize {
+            match arrows.get(&(x, y)) {
+                Some(arrow) => grid.push(*arrow),
+                None => grid += &field.heatmap[&(x, y)].to_string(),
             }
-            let new_heat = heat + field.heatmap[&new_head];
-            let new_h = new_heat + dist(new_head, target);
-            let pos = open.partition_point(|a| new_h < a.0);
-            let mut new_path = _path.clone();
-            new_path.push(head);
-            open.insert(pos, (new_h, new_head, new_dir, new_heat, new_fwd, new_path));
         }
+        grid.push('\n');
     }
-    best
+    grid
 }
 
-pub fn part_one(input: &str) -> Option<u32> {
-    let field = parse(&input);
-    least_heatloss(
-        &field,
+#[allow(dead_code)]
+fn print_debug(field: &Field, path: &[_2D]) {
+    println!("{}", render_path(field, path));
+}
+
+fn corners(field: &Field) -> (_2D, _2D) {
+    (
         (0, 0),
         (field.width as isize - 1, field.height as isize - 1),
     )
-    .into()
+}
+
+pub fn part_one(input: &str) -> Option<u32> {
+    let field = parse(&input);
+    let (start, target) = corners(&field);
+    let (heat, _path) = least_heatloss(&field, CRUCIBLE, start, target)?;
+    //print_debug(&field, &_path);
+    Some(heat)
 }
 
 pub fn part_two(input: &str) -> Option<u32> {
     let field = parse(&input);
-    least_heatloss_ultra(
-        &field,
-        (0, 0),
-        (field.width as isize - 1, field.height as isize - 1),
-    )
-    .into()
+    let (start, target) = corners(&field);
+    let (heat, _path) = least_heatloss(&field, ULTRA_CRUCIBLE, start, target)?;
+    //print_debug(&field, &_path);
+    Some(heat)
 }
 
 #[cfg(test)]
@@ -256,4 +211,38 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(94));
     }
+
+    #[test]
+    fn test_least_heatloss() {
+        let field = parse(&advent_of_code::template::read_file("examples", DAY));
+        let (start, target) = corners(&field);
+        for crucible in [CRUCIBLE, ULTRA_CRUCIBLE] {
+            let (heat, path) = least_heatloss(&field, crucible, start, target).unwrap();
+            assert_eq!(path.first(), Some(&start));
+            assert_eq!(path.last(), Some(&target));
+            let loss = path.iter().skip(1).map(|b| field.heatmap[b]).sum::<u32>();
+            assert_eq!(loss, heat);
+            let render = render_path(&field, &path);
+            assert_eq!(render.matches(['^', 'v', '<', '>']).count(), path.len() - 1);
+        }
+        // Any start and target, going back to the origin
+        let (heat, path) = least_heatloss(&field, CRUCIBLE, (5, 3), (0, 0)).unwrap();
+        assert_eq!((heat, path.len()), (23, 9));
+
+        let field = parse("111111111111\n999999999991\n999999999991\n999999999991\n999999999991");
+        let (start, target) = corners(&field);
+        let ultra = least_heatloss(&field, ULTRA_CRUCIBLE, start, target);
+        assert_eq!(ultra.map(|(heat, _)| heat), Some(71));
+        let stiff = Crucible {
+            min_run: 5,
+            max_run: 5,
+        };
+        assert_eq!(least_heatloss(&field, stiff, start, target), None);
+
+        // Blocks losing no heat: the taxicab distance alone would overestimate
+        let field = parse("01010\n94647\n40411\n70002");
+        let (start, target) = corners(&field);
+        let heat = least_heatloss(&field, CRUCIBLE, start, target).map(|(heat, _)| heat);
+        assert_eq!(heat, Some(7));
+    }
 }
@@ -1,6 +1,6 @@
 advent_of_code::solution!(18);
 
-use regex::Regex;
+use advent_of_code::polygon::Polygon;
 use std::cmp::{max, min};
 use std::collections::HashSet;
 
@@ -14,7 +14,7 @@ const EAST: _2D = (1, 0);
 type Instr = (_2D, u64, String);
 
 fn parse_one(input: &str) -> Vec<Instr> {
-    let re = Regex::new(r"(?m)^([RDLU])\s*(\d+)\s*\(#([0-9a-f]{6})\)$").unwrap();
+    let re = advent_of_code::regex!(r"(?m)^([RDLU])\s*(\d+)\s*\(#([0-9a-f]{6})\)$");
     re.captures_iter(&input)
         .map(|c| c.extract())
         .map(|(_, [d, s, c])| {
@@ -33,7 +33,7 @@ fn parse_one(input: &str) -> Vec<Instr> {
 }
 
 fn parse_two(input: &str) -> Vec<Instr> {
-    let re = Regex::new(r"(?m)^([RDLU])\s*(\d+)\s*\(#([0-9a-f]{6})\)$").unwrap();
+    let re = advent_of_code::regex!(r"(?m)^([RDLU])\s*(\d+)\s*\(#([0-9a-f]{6})\)$");
     re.captures_iter(&input)
         .map(|c| c.extract())
         .map(|(_, [_, _, c])| {
@@ -98,102 +98,16 @@ fn print_walls_scaled(instrs: &Vec<Instr>, view: (u64, u64)) {
     println!();
 }
 
-fn corners(instrs: &Vec<Instr>) -> Vec<Block> {
-    let mut head: Block = ((0, 0), (1, 1));
-    let mut corners = Vec::new();
-    for instr in instrs {
-        head = slide(head, instr.0, instr.1 as isize);
-        corners.push(head);
-    }
-    corners
-}
-
-fn perimeter(instrs: &Vec<Instr>, corners: &Vec<Block>) -> Vec<_2D> {
-    // Following corners, computes the list of points that form the outer
-    // perimeter.  Assumption: corners follow instrs in sequence and loop back.
-    // Start from any top-left corner (Z-order).
-    let (start, _) = corners
-        .iter()
-        .enumerate()
-        .min_by_key(|(_, x)| x.0 .0 + x.0 .1)
-        .unwrap();
-    let mut points = Vec::new();
-    for i in 0..corners.len() {
-        let corner_type = (
-            instrs[(start + i) % instrs.len()].0,
-            instrs[(start + i + 1) % instrs.len()].0,
-        );
-        let corner = corners[(start + i) % corners.len()];
-        let point = match corner_type {
-            // CORNER TYPE 1   | TYPE 2   => (    X    ,    Y    )
-            (NORTH, EAST) | (EAST, NORTH) => (corner.0 .0, corner.0 .1),
-            (NORTH, WEST) | (WEST, NORTH) => (corner.0 .0, corner.1 .1),
-            (SOUTH, WEST) | (WEST, SOUTH) => (corner.1 .0, corner.1 .1),
-            (SOUTH, EAST) | (EAST, SOUTH) => (corner.1 .0, corner.0 .1),
-            _ => panic!("Assumed instructions always form corners!"),
-        };
-        points.push(point);
-    }
-    points
-}
-
-fn minmax(a: isize, b: isize) -> (isize, isize) {
-    (min(a, b), max(a, b))
-}
-
-fn capacity(instrs: &Vec<Instr>) -> u64 {
-    let corners = corners(&instrs);
-    let perimeter = perimeter(&instrs, &corners);
-
-    // Scan vertically, column-by-column, in a segment-aligned way (so we can
-    // ignore vertical segments).  Add the entire block's area to the capacity
-    // when inside.
-    let mut x_breakpoints = perimeter.iter().map(|p| p.0).collect::<Vec<_>>();
-    x_breakpoints.sort_unstable();
-    x_breakpoints.dedup();
-
-    let mut horiz_segments = perimeter
-        .as_slice()
-        .windows(2)
-        .filter(|w| w[0].1 == w[1].1)
-        .map(|w| {
-            let (lx, hx) = minmax(w[0].0, w[1].0);
-            ((lx, w[0].1), (hx, w[1].1))
-        })
-        .collect::<Vec<_>>();
-    if perimeter[0].1 == perimeter[perimeter.len() - 1].1 {
-        let (lx, hx) = minmax(perimeter[0].0, perimeter[perimeter.len() - 1].0);
-        let y = perimeter[0].1;
-        horiz_segments.push(((lx, y), (hx, y)));
-    }
-    horiz_segments.sort_unstable_by_key(|k| k.0 .1);
-
-    let mut cap: u64 = 0;
-    for xs in x_breakpoints.as_slice().windows(2) {
-        let (lx, hx) = (xs[0], xs[1]);
-        let mut last_segment = horiz_segments[0];
-        let mut inside = last_segment.1 .0 > lx && hx > last_segment.0 .0;
-        // println!(
-        //     "window: ({}, {}), first segment: {:?}, inside? {}",
-        //     lx, hx, last_segment, inside
-        // );
-        for segment in horiz_segments[1..].iter() {
-            if inside {
-                cap += (segment.0 .1 - last_segment.0 .1) as u64 * (hx - lx) as u64;
-            }
-            // intersect
-            if lx < segment.1 .0 && segment.0 .0 < hx {
-                inside = !inside;
-            }
-            last_segment = *segment;
-            // println!(
-            //     "window: ({}, {}), segment: {:?}, inside? {}",
-            //     lx, hx, segment, inside
-            // );
-        }
-    }
-
-    cap
+fn capacity(instrs: &[Instr]) -> u64 {
+    // Each cube dug is a lattice point; the lagoon holds all lattice points
+    // inside and on the trench's polygon.
+    let lagoon = Polygon::from_instructions(
+        (0, 0),
+        instrs
+            .iter()
+            .map(|((x, y), len, _)| ((*x as i64, *y as i64), *len as i64)),
+    );
+    lagoon.lattice_points() as u64
 }
 
 pub fn part_one(input: &str) -> Option<u64> {
@@ -1,9 +1,8 @@
 advent_of_code::solution!(19);
 
-use regex::Regex;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Cat {
     X,
     M,
@@ -27,19 +26,14 @@ struct Workflow {
 
 type Workflows = HashMap<String, Workflow>;
 
-#[derive(Debug)]
-struct Part {
-    x: u64,
-    m: u64,
-    a: u64,
-    s: u64,
-}
+/// Ratings of a part, in the order of `Cat`.
+type Part = [u64; 4];
 type Parts = Vec<Part>;
 
 fn parse(input: &str) -> (Workflows, Parts) {
-    let rule = Regex::new(r"([xmsa])([><])(\d+):(\w+)").unwrap();
-    let workflow = Regex::new(r"(?m)^(\w+)\{((?:[xmsa][><]\d+:\w+,)+)(\w+)\}$").unwrap();
-    let part = Regex::new(r"(?m)^\{x=(\d+),m=(\d+),a=(\d+),s=(\d+)\}$").unwrap();
+    let rule = advent_of_code::regex!(r"([xmsa])([><])(\d+):(\w+)");
+    let workflow = advent_of_code::regex!(r"(?m)^(\w+)\{((?:[xmsa][><]\d+:\w+,)+)(\w+)\}$");
+    let part = advent_of_code::regex!(r"(?m)^\{x=(\d+),m=(\d+),a=(\d+),s=(\d+)\}$");
     (
         workflow
             .captures_iter(&input)
@@ -72,171 +66,327 @@ fn parse(input: &str) -> (Workflows, Parts) {
             })
             .collect::<Workflows>(),
         part.captures_iter(&input)
-            .map(|cap| Part {
-                x: cap[1].parse::<u64>().unwrap(),
-                m: cap[2].parse::<u64>().unwrap(),
-                a: cap[3].parse::<u64>().unwrap(),
-                s: cap[4].parse::<u64>().unwrap(),
-            })
+            .map(|cap| [1, 2, 3, 4].map(|i| cap[i].parse::<u64>().unwrap()))
             .collect::<Parts>(),
     )
 }
 
-fn sort_parts(workflows: &Workflows, parts: &Parts) -> u64 {
-    let mut sum = 0;
-    for part in parts {
-        let mut target = "in";
-        while target != "A" && target != "R" {
-            let workflow = &workflows[target];
-            let mut rule_target = "";
-            for rule in workflow.rules.iter() {
-                let part_val = match rule.cat {
-                    Cat::A => part.a,
-                    Cat::M => part.m,
-                    Cat::S => part.s,
-                    Cat::X => part.x,
-                };
-                if rule.less && part_val < rule.val || !rule.less && part_val > rule.val {
-                    rule_target = &rule.target;
-                    break;
-                }
-            }
-            if rule_target.is_empty() {
-                target = &workflow.target;
-            } else {
-                target = rule_target;
-            }
+/* -------------------------------------------------------------------------- */
+
+type NodeId = usize;
+const ACCEPT: NodeId = 0;
+const REJECT: NodeId = 1;
+
+/// A rule of a workflow, as a node of the decision tree.
+#[derive(Debug)]
+struct Test {
+    cat: Cat,
+    less: bool,
+    val: u64,
+    pass: NodeId,
+    fail: NodeId,
+    workflow: String,
+    rule: usize,
+    last: bool, // failing goes to the workflow's default target
+}
+
+#[derive(Debug)]
+enum Node {
+    Accept,
+    Reject,
+    Test(Test),
+}
+
+/// Ratings from `[0]` (included) to `[1]` (excluded), in the order of `Cat`.
+type Ranges = [[u64; 2]; 4];
+
+const ALL_RATINGS: Ranges = [[1, 4001]; 4];
+
+fn volume(ranges: &Ranges) -> u64 {
+    ranges.iter().map(|[lo, hi]| hi - lo).product()
+}
+
+fn intersection(a: &Ranges, b: &Ranges) -> Option<Ranges> {
+    let mut ranges = *a;
+    for (r, [lo, hi]) in ranges.iter_mut().zip(b) {
+        *r = [r[0].max(*lo), r[1].min(*hi)];
+        if r[0] >= r[1] {
+            return None;
         }
-        if target == "A" {
-            sum += part.a + part.m + part.s + part.x;
+    }
+    Some(ranges)
+}
+
+impl Test {
+    fn matches(&self, part: &Part) -> bool {
+        let rating = part[self.cat as usize];
+        if self.less {
+            rating < self.val
+        } else {
+            rating > self.val
         }
     }
-    sum
+
+    /// Splits `ranges` into the ratings that match, and the ones that don't.
+    fn split(&self, ranges: &Ranges) -> (Option<Ranges>, Option<Ranges>) {
+        let mut low = ALL_RATINGS;
+        let mut high = ALL_RATINGS;
+        let cut = if self.less { self.val } else { self.val + 1 };
+        low[self.cat as usize][1] = cut;
+        high[self.cat as usize][0] = cut;
+        let (low, high) = (intersection(ranges, &low), intersection(ranges, &high));
+        if self.less {
+            (low, high)
+        } else {
+            (high, low)
+        }
+    }
+
+    fn label(&self) -> String {
+        let cat = format!("{:?}", self.cat).to_lowercase();
+        let cmp = if self.less { '<' } else { '>' };
+        format!(
+            "{}[{}]: {}{}{}",
+            self.workflow, self.rule, cat, cmp, self.val
+        )
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct PartInt {
-    x: [u64; 2],
-    m: [u64; 2],
-    a: [u64; 2],
-    s: [u64; 2],
+/// One workflow visited by a part, with the rule that sent it to the next one
+/// (`None` for the workflow's default target).
+#[derive(Debug, PartialEq)]
+struct Step<'a> {
+    workflow: &'a str,
+    rule: Option<usize>,
 }
 
+/// Why a rule can be removed without changing the outcome of any part.
 #[derive(Debug, PartialEq)]
-enum Outcome {
-    No,
-    Low,
-    High,
-    Yes,
+enum Dead {
+    Unreachable,  // no part gets to the rule
+    NeverMatches, // parts get to the rule, but none matches it
+    Redundant,    // matching parts have the same outcome without the rule
+}
+
+/// Workflows compiled into a DAG of tests: each rule is a node, which goes to
+/// the first rule of its target workflow when it matches, and to the next
+/// rule of its own workflow (or the default target) otherwise. Workflows must
+/// not loop.
+#[derive(Debug)]
+struct DecisionTree {
+    nodes: Vec<Node>,
+    root: NodeId,
 }
 
-fn accepted_intervals(workflows: &Workflows) -> Vec<PartInt> {
-    let init = PartInt {
-        x: [0, 4000], // 1..=4000 - 1 -> 0..4000; Need to adjust all checks by -1
-        m: [0, 4000],
-        a: [0, 4000],
-        s: [0, 4000],
-    };
-    // Reduce part intervals by going throuh all workflows, starting from "in".
-    // Manage a list of open (workflow, part interval), which should be sorted
-    // in final "accepted" bucket.  Since each rule splits a part interval in
-    // distinct non-overlapping interval, there's no need to worry about
-    // overlaps.
-    let mut open = vec![(init, "in")];
-    let mut accepted = vec![];
-    while !open.is_empty() {
-        let (mut partint, target) = open.pop().unwrap();
-        if target == "A" {
-            accepted.push(partint);
-            continue;
+impl DecisionTree {
+    fn compile(workflows: &Workflows) -> Self {
+        // Number rules first, so that targets can be resolved in one pass
+        let mut first = HashMap::from([("A", ACCEPT), ("R", REJECT)]);
+        let mut next_id = 2;
+        let mut names = workflows.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        for name in names.iter() {
+            first.insert(name, next_id);
+            next_id += workflows[*name].rules.len();
         }
-        if target == "R" {
-            continue; // discard
+        let mut nodes = vec![Node::Accept, Node::Reject];
+        for name in names.iter() {
+            let workflow = &workflows[*name];
+            for (i, rule) in workflow.rules.iter().enumerate() {
+                let last = i + 1 == workflow.rules.len();
+                nodes.push(Node::Test(Test {
+                    cat: rule.cat,
+                    less: rule.less,
+                    val: rule.val,
+                    pass: first[rule.target.as_str()],
+                    fail: match last {
+                        true => first[workflow.target.as_str()],
+                        false => first[name] + i + 1,
+                    },
+                    workflow: name.to_string(),
+                    rule: i,
+                    last,
+                }));
+            }
         }
-        let workflow = &workflows[target];
-        // Split interval with each rules
-        let mut outcome = Outcome::No;
-        for rule in workflow.rules.iter() {
-            let part_val = match rule.cat {
-                Cat::A => partint.a,
-                Cat::M => partint.m,
-                Cat::S => partint.s,
-                Cat::X => partint.x,
-            };
-            let test_val = rule.val - 1; // 0..4000 interval
-            outcome = if rule.less {
-                if part_val[1] < test_val {
-                    Outcome::Yes
-                } else {
-                    if part_val[0] < test_val {
-                        Outcome::Low
-                    } else {
-                        Outcome::No
-                    }
-                }
-            } else {
-                if part_val[0] > test_val {
-                    Outcome::Yes
-                } else {
-                    if part_val[1] > test_val {
-                        Outcome::High
-                    } else {
-                        Outcome::No
+        Self {
+            nodes,
+            root: first["in"],
+        }
+    }
+
+    fn tests(&self) -> impl Iterator<Item = (NodeId, &Test)> + '_ {
+        self.nodes.iter().enumerate().filter_map(|(id, n)| match n {
+            Node::Test(test) => Some((id, test)),
+            _ => None,
+        })
+    }
+
+    /// Whether `part` is accepted, and the workflows it went through.
+    fn evaluate(&self, part: &Part) -> (bool, Vec<Step<'_>>) {
+        let mut path = vec![];
+        let mut id = self.root;
+        loop {
+            match &self.nodes[id] {
+                Node::Accept => return (true, path),
+                Node::Reject => return (false, path),
+                Node::Test(test) => {
+                    let matches = test.matches(part);
+                    if matches || test.last {
+                        path.push(Step {
+                            workflow: &test.workflow,
+                            rule: matches.then_some(test.rule),
+                        });
                     }
+                    id = if matches { test.pass } else { test.fail };
                 }
-            };
-            match outcome {
-                Outcome::Yes => {
-                    // The whole interval matches!
-                    open.push((partint, &rule.target));
-                    break;
+            }
+        }
+    }
+
+    /// Disjoint ranges of ratings, within `ranges`, accepted from node `id`.
+    fn accepted_from(&self, id: NodeId, ranges: Ranges, accepted: &mut Vec<Ranges>) {
+        match &self.nodes[id] {
+            Node::Accept => accepted.push(ranges),
+            Node::Reject => {}
+            Node::Test(test) => {
+                let (pass, fail) = test.split(&ranges);
+                if let Some(pass) = pass {
+                    self.accepted_from(test.pass, pass, accepted);
                 }
-                Outcome::No => {}
-                Outcome::Low => {
-                    // The lower half matches with "less than" test
-                    let mut copy = partint;
-                    match rule.cat {
-                        Cat::A => (partint.a[0], copy.a[1]) = (test_val, test_val),
-                        Cat::M => (partint.m[0], copy.m[1]) = (test_val, test_val),
-                        Cat::S => (partint.s[0], copy.s[1]) = (test_val, test_val),
-                        Cat::X => (partint.x[0], copy.x[1]) = (test_val, test_val),
-                    };
-                    open.push((copy, &rule.target));
+                if let Some(fail) = fail {
+                    self.accepted_from(test.fail, fail, accepted);
                 }
-                Outcome::High => {
-                    // The upper half matches with "greater than" test
-                    let mut copy = partint;
-                    match rule.cat {
-                        Cat::A => (partint.a[1], copy.a[0]) = (test_val + 1, test_val + 1),
-                        Cat::M => (partint.m[1], copy.m[0]) = (test_val + 1, test_val + 1),
-                        Cat::S => (partint.s[1], copy.s[0]) = (test_val + 1, test_val + 1),
-                        Cat::X => (partint.x[1], copy.x[0]) = (test_val + 1, test_val + 1),
-                    };
-                    open.push((copy, &rule.target));
+            }
+        }
+    }
+
+    fn accepted(&self, ranges: Ranges) -> Vec<Ranges> {
+        let mut accepted = vec![];
+        self.accepted_from(self.root, ranges, &mut accepted);
+        accepted
+    }
+
+    /// Number of accepted combinations of ratings within `ranges`.
+    fn combinations(&self, ranges: Ranges) -> u64 {
+        self.accepted(ranges).iter().map(volume).sum()
+    }
+
+    /// Ranges of ratings, within `ranges`, that get to each node.
+    fn reached(&self, ranges: Ranges) -> Vec<Vec<Ranges>> {
+        let mut reached = vec![vec![]; self.nodes.len()];
+        let mut open = vec![(self.root, ranges)];
+        while let Some((id, ranges)) = open.pop() {
+            reached[id].push(ranges);
+            if let Node::Test(test) = &self.nodes[id] {
+                let (pass, fail) = test.split(&ranges);
+                open.extend(pass.map(|r| (test.pass, r)));
+                open.extend(fail.map(|r| (test.fail, r)));
+            }
+        }
+        reached
+    }
+
+    /// Rules that can be removed without changing the outcome of any part with
+    /// ratings within `ranges`, as (workflow, rule index, reason).
+    fn dead_rules(&self, ranges: Ranges) -> Vec<(&str, usize, Dead)> {
+        let reached = self.reached(ranges);
+        let mut dead = vec![];
+        for (id, test) in self.tests() {
+            let matching = reached[id]
+                .iter()
+                .filter_map(|r| test.split(r).0)
+                .collect::<Vec<_>>();
+            let reason = if reached[id].is_empty() {
+                Dead::Unreachable
+            } else if matching.is_empty() {
+                Dead::NeverMatches
+            } else {
+                // Matching parts are accepted the same way whether they go to
+                // the rule's target or fall through, if both sets of accepted
+                // ranges have the same volume as their intersection.
+                let accepted = |to: NodeId| {
+                    let mut accepted = vec![];
+                    for r in matching.iter() {
+                        self.accepted_from(to, *r, &mut accepted);
+                    }
+                    accepted
+                };
+                let (pass, fail) = (accepted(test.pass), accepted(test.fail));
+                let both = pass
+                    .iter()
+                    .flat_map(|p| fail.iter().filter_map(|f| intersection(p, f)))
+                    .map(|r| volume(&r))
+                    .sum::<u64>();
+                let (pass, fail) = (
+                    pass.iter().map(volume).sum::<u64>(),
+                    fail.iter().map(volume).sum::<u64>(),
+                );
+                if pass != both || fail != both {
+                    continue;
                 }
+                Dead::Redundant
             };
+            dead.push((test.workflow.as_str(), test.rule, reason));
         }
-        // If rules outcome != Yes, continue to workflow target
-        if outcome != Outcome::Yes {
-            open.push((partint, &workflow.target));
+        dead
+    }
+
+    /// The tree in Graphviz DOT format.
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph workflows {\n");
+        dot += "  A [shape=box, color=green];\n  R [shape=box, color=red];\n";
+        let name = |id: NodeId| match id {
+            ACCEPT => "A".to_string(),
+            REJECT => "R".to_string(),
+            id => format!("n{id}"),
+        };
+        for (id, test) in self.tests() {
+            dot += &format!("  n{id} [label=\"{}\"];\n", test.label());
+            dot += &format!("  n{id} -> {} [label=yes];\n", name(test.pass));
+            dot += &format!("  n{id} -> {} [label=no];\n", name(test.fail));
         }
+        dot += &format!(
+            "  start [shape=point];\n  start -> {};\n}}\n",
+            name(self.root)
+        );
+        dot
     }
-    accepted
+}
+
+#[allow(dead_code)]
+fn print_path(accepted: bool, path: &[Step]) {
+    for step in path {
+        match step.rule {
+            Some(rule) => print!("{}[{}] -> ", step.workflow, rule),
+            None => print!("{} -> ", step.workflow),
+        }
+    }
+    println!("{}", if accepted { "A" } else { "R" });
+}
+
+#[allow(dead_code)]
+fn print_analysis(tree: &DecisionTree) {
+    for (workflow, rule, reason) in tree.dead_rules(ALL_RATINGS) {
+        println!("{workflow}[{rule}]: {reason:?}");
+    }
+    print!("{}", tree.to_dot());
 }
 
 pub fn part_one(input: &str) -> Option<u64> {
     let (workflows, parts) = parse(&input);
-    Some(sort_parts(&workflows, &parts))
+    let tree = DecisionTree::compile(&workflows);
+    parts
+        .iter()
+        .filter(|part| tree.evaluate(part).0)
+        .map(|part| part.iter().sum::<u64>())
+        .sum::<u64>()
+        .into()
 }
 
 pub fn part_two(input: &str) -> Option<u64> {
     let (workflows, _) = parse(&input);
-    let accepted = accepted_intervals(&workflows);
-    accepted
-        .iter()
-        .map(|p| (p.x[1] - p.x[0]) * (p.m[1] - p.m[0]) * (p.a[1] - p.a[0]) * (p.s[1] - p.s[0]))
-        .sum::<u64>()
-        .into()
+    Some(DecisionTree::compile(&workflows).combinations(ALL_RATINGS))
 }
 
 #[cfg(test)]
@@ -254,4 +404,48 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(167409079868000));
     }
+
+    #[test]
+    fn test_decision_tree() {
+        let (workflows, parts) = parse(&advent_of_code::template::read_file("examples", DAY));
+        let tree = DecisionTree::compile(&workflows);
+        let step = |workflow, rule| Step { workflow, rule };
+        assert_eq!(
+            tree.evaluate(&parts[0]),
+            (
+                true,
+                vec![
+                    step("in", None),
+                    step("qqz", Some(0)),
+                    step("qs", None),
+                    step("lnx", Some(0))
+                ]
+            )
+        );
+        let (mut low, mut high) = (ALL_RATINGS, ALL_RATINGS);
+        (low[Cat::S as usize], high[Cat::S as usize]) = ([1, 1351], [1351, 4001]);
+        assert_eq!(
+            tree.combinations(low) + tree.combinations(high),
+            167409079868000
+        );
+        let ranges = [[1, 11], [1000, 1010], [2000, 2010], [1340, 1360]];
+        let accepted = (1..11)
+            .flat_map(|x| (1000..1010).map(move |m| [x, m]))
+            .flat_map(|[x, m]| (2000..2010).map(move |a| [x, m, a]))
+            .flat_map(|[x, m, a]| (1340..1360).map(move |s| [x, m, a, s]))
+            .filter(|part| tree.evaluate(part).0)
+            .count();
+        assert_eq!(tree.combinations(ranges), accepted as u64);
+        assert_eq!(
+            tree.dead_rules(ALL_RATINGS),
+            vec![
+                ("gd", 0, Dead::Redundant),
+                ("lnx", 0, Dead::Redundant),
+                ("qs", 0, Dead::Redundant)
+            ]
+        );
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("[label=\"in[0]: s<1351\"]"));
+    }
 }
@@ -1,242 +1,204 @@
 advent_of_code::solution!(20);
 
-use num::integer::lcm;
-use std::collections::{HashMap, VecDeque};
+use advent_of_code::circuit::{Kind, ModuleState, Network, Pulse, Simulator, BROADCASTER};
+use advent_of_code::progression::{intersect_all, Progression};
+use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Copy, Clone)]
-enum Cat {
-    Broad,
-    Flip,
-    Con,
+fn parse(input: &str) -> Option<Network> {
+    Network::parse(input)
+        .map_err(|e| eprintln!("Invalid input: {e}"))
+        .ok()
 }
 
-#[derive(Debug)]
-struct Mod {
-    cat: Cat,
-    dests: Vec<String>,
-}
-
-#[derive(Debug, PartialEq, Copy, Clone)]
-enum Pulse {
-    Low,
-    High,
+pub fn part_one(input: &str) -> Option<u64> {
+    let network = parse(input)?;
+    let mut sim = Simulator::new(&network);
+    for _ in 0..1000 {
+        sim.press();
+    }
+    let (low, high) = sim.pulses_sent();
+    Some(low * high)
 }
 
-type FlipStates = HashMap<String, bool>;
-type ConStates = HashMap<String, HashMap<String, Pulse>>;
-type Mods = HashMap<String, Mod>;
-
-fn parse(input: &str) -> Mods {
-    input
-        .lines()
-        .map(|l| {
-            let (module, dests) = l.trim().split_once(" -> ").unwrap();
-            let (cat, address) = match &module[0..1] {
-                "b" => (Cat::Broad, "broadcast".to_string()),
-                "%" => (Cat::Flip, module[1..].to_string()),
-                "&" => (Cat::Con, module[1..].to_string()),
-                _ => panic!("(╯°□°)╯︵ ┻━┻"),
-            };
-            (
-                address,
-                Mod {
-                    cat,
-                    dests: dests.split(", ").map(|s| s.to_string()).collect::<Vec<_>>(),
-                },
-            )
-        })
-        .collect::<Mods>()
+/// Why the circuit could not be analysed.
+#[derive(Debug)]
+enum Unsolvable {
+    NoFeeder(String),
+    NotAConjunction(String),
+    FeedsBack(String),
+    NoCycle(usize),
+    NeverAligned,
 }
 
-fn states_init(mods: &Mods) -> (FlipStates, ConStates) {
-    let mut flips = FlipStates::new();
-    let mut cons = ConStates::new();
-    for (k, v) in mods.iter() {
-        match v.cat {
-            Cat::Broad => {}
-            Cat::Flip => {
-                flips.insert(k.to_string(), false);
+impl std::fmt::Display for Unsolvable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoFeeder(output) => write!(f, "{output} must be fed by exactly one module"),
+            Self::NotAConjunction(name) => {
+                write!(
+                    f,
+                    "{name} must be a conjunction to be split in sub-circuits"
+                )
             }
-            Cat::Con => {
-                cons.insert(k.to_string(), HashMap::new());
+            Self::FeedsBack(name) => write!(f, "{name} feeds back into its own inputs"),
+            Self::NoCycle(presses) => {
+                write!(f, "a sub-circuit did not cycle within {presses} presses")
             }
+            Self::NeverAligned => write!(f, "sub-circuits never fire in the same press"),
         }
     }
-    // Set states for ConStates, iterate through all dests
-    for (k, v) in mods.iter() {
-        for dest in v.dests.iter() {
-            if let Some(con) = cons.get_mut(dest) {
-                con.insert(k.to_string(), Pulse::Low);
+}
+
+const MAX_PRESSES: usize = 1 << 16;
+
+/// `from` and all modules it depends on, excluding the broadcaster which
+/// sends the same pulses on each press.
+fn upstream(network: &Network, from: usize) -> HashSet<usize> {
+    let mut seen = HashSet::from([from]);
+    let mut open = vec![from];
+    while let Some(here) = open.pop() {
+        for src in network.module(here).inputs() {
+            if *src != BROADCASTER && seen.insert(*src) {
+                open.push(*src);
             }
         }
     }
-    (flips, cons)
+    seen
 }
 
-fn send_pulse(
-    mods: &Mods,
-    flips: &mut FlipStates,
-    cons: &mut ConStates,
-    watch: Option<&str>,
-) -> (u64, u64, HashMap<String, (u64, u64)>) {
-    let mut count_low = 0;
-    let mut count_high = 0;
-    let mut pending = VecDeque::from([(Pulse::Low, "button".to_string(), "broadcast".to_string())]);
-    let mut watch_list = HashMap::new();
-    while !pending.is_empty() {
-        let (pulse, src, here) = pending.pop_front().unwrap();
-        // println!("{} --{:?}-> {}", src, pulse, here);
-        match pulse {
-            Pulse::High => count_high += 1,
-            Pulse::Low => count_low += 1,
-        }
-        if let Some(w) = watch {
-            if here == w {
-                let (inc_low, inc_high) = match pulse {
-                    Pulse::High => (0, 1),
-                    Pulse::Low => (1, 0),
-                };
-                watch_list
-                    .entry(src.clone())
-                    .and_modify(|(low, high)| {
-                        *low += inc_low;
-                        *high += inc_high;
-                    })
-                    .or_insert((inc_low, inc_high));
-            }
+/// A group of modules that drives some inputs of the final conjunction, and
+/// shares no module with any other group.
+struct SubCircuit {
+    inputs: Vec<usize>,
+    modules: Vec<usize>,
+    seen: HashMap<(Vec<ModuleState>, Vec<Pulse>), usize>,
+    hits: Vec<usize>,
+    cycle: Option<(usize, usize)>,
+}
+
+impl SubCircuit {
+    /// States of the sub-circuit's modules, with the pulses the final
+    /// conjunction last received from the sub-circuit only: its other slots
+    /// belong to other sub-circuits.
+    fn state(&self, last: usize, sim: &Simulator) -> (Vec<ModuleState>, Vec<Pulse>) {
+        let ModuleState::Conjunction(memory) = sim.query(last) else {
+            unreachable!()
+        };
+        (
+            self.modules.iter().map(|m| sim.query(*m)).collect(),
+            self.inputs.iter().map(|i| memory[*i]).collect(),
+        )
+    }
+
+    fn presses(&self) -> Vec<Progression> {
+        let (start, len) = self.cycle.unwrap();
+        self.hits
+            .iter()
+            .map(|h| match *h > start {
+                true => (*h as u64, len as u64),
+                false => (*h as u64, 0),
+            })
+            .collect()
+    }
+}
+
+fn analyse(network: &Network, output: &str) -> Result<usize, Unsolvable> {
+    // The output receives a low pulse when the conjunction feeding it has seen
+    // high pulses from all of its inputs. Group these inputs in sub-circuits
+    // that share no module: each evolves on its own and eventually cycles.
+    // Measure each cycle, with the presses at which the sub-circuit shows
+    // only high pulses to the conjunction, then find the first press common
+    // to all sub-circuits with CRT. This assumes that when sub-circuits fire
+    // during the same press, the conjunction sees all of them at once.
+    let last = match network.id(output).map(|o| network.module(o).inputs()) {
+        Some([last]) => *last,
+        _ => return Err(Unsolvable::NoFeeder(output.to_string())),
+    };
+    let name = |id: usize| network.module(id).name.to_string();
+    if network.module(last).kind != Kind::Conjunction {
+        return Err(Unsolvable::NotAConjunction(name(last)));
+    }
+    let mut groups: Vec<(Vec<usize>, HashSet<usize>)> = Vec::new();
+    for input in network.module(last).inputs() {
+        let mut modules = upstream(network, *input);
+        if modules.contains(&last) {
+            return Err(Unsolvable::FeedsBack(name(last)));
         }
-        if !mods.contains_key(&here) {
-            // Handles "rx" modules, e.g.
-            continue;
+        let mut inputs = vec![*input];
+        while let Some(i) = groups.iter().position(|(_, g)| !g.is_disjoint(&modules)) {
+            let (other_inputs, other_modules) = groups.swap_remove(i);
+            inputs.extend(other_inputs);
+            modules.extend(other_modules);
         }
-        let module = &mods[&here];
-        match module.cat {
-            Cat::Broad => {
-                // Broadcast the *same* pulse to dest
-                for dest in module.dests.iter() {
-                    pending.push_back((pulse, here.clone(), dest.to_string()));
-                }
-            }
-            Cat::Con => {
-                // Remember most recent pulse, if all high -> low pulse, else -> high pulse
-                cons.get_mut(&here)
-                    .unwrap()
-                    .entry(src)
-                    .and_modify(|v| *v = pulse);
-                let inv = match cons[&here].values().all(|v| *v == Pulse::High) {
-                    true => Pulse::Low,
-                    false => Pulse::High,
-                };
-                for dest in module.dests.iter() {
-                    pending.push_back((inv, here.clone(), dest.to_string()));
-                }
+        groups.push((inputs, modules));
+    }
+    // Inputs are tracked by their slot in the conjunction's memory
+    let slots = network.module(last).inputs();
+    let slot = |input: usize| slots.iter().position(|i| *i == input).unwrap();
+
+    let mut sim = Simulator::new(network);
+    let mut circuits = groups
+        .into_iter()
+        .map(|(inputs, modules)| SubCircuit {
+            inputs: inputs.into_iter().map(slot).collect(),
+            modules: modules.into_iter().collect(),
+            seen: HashMap::new(),
+            hits: Vec::new(),
+            cycle: None,
+        })
+        .collect::<Vec<_>>();
+    for c in circuits.iter_mut() {
+        c.seen.insert(c.state(last, &sim), 0);
+    }
+    let mut hits = vec![false; circuits.len()];
+    for press in 1..=MAX_PRESSES {
+        let ModuleState::Conjunction(mut memory) = sim.query(last) else {
+            unreachable!()
+        };
+        hits.fill(false);
+        sim.push_button();
+        while let Some(event) = sim.step() {
+            if event.dest != last {
+                continue;
             }
-            Cat::Flip => {
-                // Ignore high pulse, flip on low pulse, true -> high pulse, false -> low pulse
-                if pulse == Pulse::High {
-                    continue;
-                }
-                flips.entry(here.clone()).and_modify(|v| *v = !*v);
-                let same = match flips[&here] {
-                    true => Pulse::High,
-                    false => Pulse::Low,
-                };
-                for dest in module.dests.iter() {
-                    pending.push_back((same, here.clone(), dest.to_string()));
-                }
+            memory[slot(event.src)] = event.pulse;
+            for (c, hit) in circuits.iter().zip(hits.iter_mut()) {
+                *hit |= c.inputs.iter().all(|i| memory[*i] == Pulse::High);
             }
         }
-    }
-    //println!("{}, {}", count_low, count_high);
-    (count_low, count_high, watch_list)
-}
-
-#[allow(dead_code)]
-fn print_graph(modules: &Mods) {
-    let mut keys = modules.keys().collect::<Vec<_>>();
-    keys.sort();
-    for key in keys {
-        let m = &modules[key];
-        match m.cat {
-            Cat::Broad => {}
-            Cat::Con => {
-                print!("&");
+        for (c, hit) in circuits.iter_mut().zip(hits.iter()) {
+            if c.cycle.is_some() {
+                continue;
             }
-            Cat::Flip => {
-                print!("%");
+            if *hit {
+                c.hits.push(press);
+            }
+            let state = c.state(last, &sim);
+            if let Some(start) = c.seen.insert(state, press) {
+                c.cycle = Some((start, press - start));
             }
         }
-        print!("{} -> {}", key, m.dests[0]);
-        for d in &m.dests[1..] {
-            print!(", {}", d);
+        if circuits.iter().all(|c| c.cycle.is_some()) {
+            break;
         }
-        println!();
     }
-}
+    if circuits.iter().any(|c| c.cycle.is_none()) {
+        return Err(Unsolvable::NoCycle(MAX_PRESSES));
+    }
 
-pub fn part_one(input: &str) -> Option<u64> {
-    let mods = parse(&input);
-    //print_graph(&mods);
-    let (mut flipstates, mut constates) = states_init(&mods);
-    let (low, high) = (0..1000)
-        .map(|_| send_pulse(&mods, &mut flipstates, &mut constates, None))
-        .fold((0, 0), |acc, e| (e.0 + acc.0, e.1 + acc.1));
-    Some(low * high)
+    // Intersect presses of all sub-circuits; starting from all presses >= 1
+    intersect_all((1, 1), circuits.iter().map(SubCircuit::presses))
+        .iter()
+        .map(|(first, _)| *first as usize)
+        .min()
+        .ok_or(Unsolvable::NeverAligned)
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
-    let mods = parse(&input);
-    //print_graph(&mods);
-    // I do not know how to solve this in the general case.  After pondering for
-    // a while what is the solution, I just looked at the tree of modules that
-    // output to rx:
-    //
-    //       0      1       2      3...
-    // rx <- &gf <- &qs <- &mh <- %nr, %jh, ...
-    //           <- &sv <- &jt <- %nk, ...
-    //           <- &pg <- &pz <- ...
-    //           <- &sp <- &rn <- ...
-    //
-    // At the third level, it's a long serie of %flip modules.  So if all these
-    // layer-3 modules most recent pulses where high pulses, the layer-2 &conj
-    // modules will all send a low pulse, which will get inverted to high pulse
-    // by layer-1s and result in layer-0 &gf sending a low pulse.
-    //
-    // So it's clear that layer-1 sends mostly a bunch of low pulses, and once
-    // in a while, when all the layer-3 modules sync on a high pulse, layer-0
-    // receives a single high pulse.
-    //
-    // Therefore in the code below, we're going to simply watch the frequency at
-    // which qs, sv, pg and sp send a high pulse to gf, and bet it's periodic,
-    // then compute the LCM of the period.
-    let (mut flipstates, mut constates) = states_init(&mods);
-
-    let mut first_high_pulse: HashMap<String, usize> = HashMap::new();
-    let mut press_count = 0;
-
-    let output = "rx"; // For a slightly more general solution, find "rx"'s source.
-    let watch = mods
-        .iter()
-        .find(|(_, m)| m.dests.contains(&output.to_owned()))
-        .unwrap()
-        .0;
-    let watch_len = constates[watch].len();
-    while first_high_pulse.len() < watch_len {
-        press_count += 1;
-        let (_, _, src_count) = send_pulse(&mods, &mut flipstates, &mut constates, Some(watch));
-
-        for (src, (_, high)) in src_count.iter() {
-            if *high > 0 {
-                assert!(*high == 1); // Test assumptions
-                let first = first_high_pulse
-                    .entry(src.to_string())
-                    .or_insert(press_count);
-                assert!(press_count % *first == 0); // Test assumptions
-            }
-        }
-    }
-
-    Some(first_high_pulse.values().fold(1, |a, e| lcm(a, *e)))
+    let network = parse(input)?;
+    analyse(&network, "rx")
+        .map_err(|e| eprintln!("Cannot solve: {e}"))
+        .ok()
 }
 
 #[cfg(test)]
@@ -254,4 +216,44 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(1));
     }
+
+    fn analyse_input(input: &str, output: &str) -> Result<usize, Unsolvable> {
+        analyse(&parse(input).unwrap(), output)
+    }
+
+    #[test]
+    fn test_offsets() {
+        // a fires at odd presses; e when the b, c counter reaches 3, at
+        // presses 3, 7, 11...: the first common press is not the lcm of 2, 4
+        let input = "broadcaster -> a, b\n%a -> con\n%b -> c, d\n%c -> d\n\
+            &d -> e\n&e -> con\n&con -> rx";
+        assert!(matches!(analyse_input(input, "rx"), Ok(3)));
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        let input = "broadcaster -> a, b\n%a -> rx\n%b -> rx";
+        assert!(matches!(analyse_input(input, "rx"), Err(Unsolvable::NoFeeder(o)) if o == "rx"));
+        assert!(matches!(
+            analyse_input(input, "zz"),
+            Err(Unsolvable::NoFeeder(_))
+        ));
+
+        let input = "broadcaster -> a\n%a -> rx";
+        let result = analyse_input(input, "rx");
+        assert!(matches!(result, Err(Unsolvable::NotAConjunction(n)) if n == "a"));
+
+        let input = "broadcaster -> a\n%a -> con\n&con -> a, rx";
+        let result = analyse_input(input, "rx");
+        assert!(matches!(result, Err(Unsolvable::FeedsBack(n)) if n == "con"));
+
+        // a fires at odd presses; k briefly at presses 6, 10, 14..., when c
+        // turns on and g sees it before h turns off
+        let input = "broadcaster -> a, b\n%a -> con\n%b -> c\n%c -> g, h\n&h -> g\n\
+            &g -> k\n&k -> con\n&con -> rx";
+        assert!(matches!(
+            analyse_input(input, "rx"),
+            Err(Unsolvable::NeverAligned)
+        ));
+    }
 }
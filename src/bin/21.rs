@@ -1,6 +1,6 @@
 advent_of_code::solution!(21);
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 type _2D = (isize, isize);
 
@@ -142,105 +142,205 @@ fn generalized_reach(field: &Field, start: _2D, step_count: usize, clip: bool) -
     }
 }
 
-fn clipping_reach(field: &Field, start: _2D, step_count: usize) -> usize {
-    generalized_reach(field, start, step_count, true).len()
+/// Number of (i, j), with i, j >= 0, such that i * w + j * h <= t and has the
+/// same parity as t, for positive w and h.
+fn quadrant_count(t: u64, w: u64, h: u64) -> u64 {
+    assert!(w > 0 && h > 0, "strides must be positive");
+    // Split i and j by parity: i = 2a + pi and j = 2b + pj, then the parity of
+    // i * w + j * h is the one of pi * w + pj * h, and we count (a, b) pairs
+    // under a line, which is a sum of floors.
+    let mut count = 0;
+    for base in [0, w, h, w + h] {
+        if base > t || (t - base) % 2 != 0 {
+            continue;
+        }
+        let t = t - base;
+        let a_max = t / (2 * w);
+        count += floor_sum(a_max + 1, 2 * h, 2 * w, t - 2 * w * a_max) + a_max + 1;
+    }
+    count
 }
 
-fn special_reach(field: &Field, step_count: usize) -> usize {
-    // This solution is special to the problem, it has 3 assumptions:
-    //
-    //  - fields are square (blocks), with odd length (2k + 1)
-    //  - start is fully centered (at k), due to odd length sides
-    //  - the layout of rocks lets you use manahattan distance between fields.
-    //  - the number of steps from the start modulo size of the field is
-    //    exactly 2k, i.e. the number of steps ends at the edge of the field.
-    //
-    // The example given does not fit the above, and actually makes things
-    // harder. With the assumptions above, there are only a finite set of blocks
-    // that are laid out in this fashion around the fully filled center blocks
-    // (S, .), as shown for radius = 3:
-    //
-    //       aAb
-    //      aX.Zb
-    //     aX...Zb
-    //     C..S..D
-    //     dW...Yc
-    //      dW.Yc
-    //       dBc
-    //
-    //  A, B, C, D:    a, b, c, d:    W, X, Y, Z:
-    //
-    //   ...O...        .......        .O.O...
-    //   ..O.O..        .......        O.O.O..
-    //   .O.O.O.        .......        .O.O.O.
-    //   O.O.O.O        .......        O.O.O.O
-    //   .O.O.O.        O......        .O.O.O.
-    //   O.O.O.O        .O.....        O.O.O.O
-    //   .O.O.O.        O.O....        .O.O.O.
-    //
-    // We start by verifying some of the assumptions:
-    assert_eq!(field.width, field.height);
-    let side = field.width as isize;
-    let steps = step_count as isize;
-    assert_eq!(field.start.0 * 2 + 1, side);
-    assert_eq!(field.start.1 * 2 + 1, side);
-    assert!((field.start.0 - steps).rem_euclid(side) == 0);
-    assert!((field.start.1 - steps).rem_euclid(side) == 0);
-    let radius = {
-        let (q, r) = num::integer::div_rem(steps + field.start.0, side);
-        assert!(r == side - 1);
-        q
-    };
-    assert!(radius > 1);
+/// Number of k >= 0 such that k * w <= t and has the same parity as t, for a
+/// positive w.
+fn ray_count(t: u64, w: u64) -> u64 {
+    assert!(w > 0, "stride must be positive");
+    [0, w]
+        .iter()
+        .filter(|base| **base <= t && (t - **base) % 2 == 0)
+        .map(|base| (t - base) / (2 * w) + 1)
+        .sum()
+}
 
-    // Compute center blocks, which are alternating series of even/odd at radius
-    // - 1 from the S block (in manhattan distance):
-    //
-    // first serie: 8 + 16 + .. + (2k * 4)
-    // other serie: 4 + 12 + .. + ((2k + 1) * 4)
-    let even_reach = clipping_reach(field, field.start, field.width * 2);
-    let odd_reach = clipping_reach(field, field.start, field.width * 2 + 1);
-    let (serie_1_reach, serie_2_reach) = if step_count % 2 == 0 {
-        (even_reach, odd_reach)
-    } else {
-        (odd_reach, even_reach)
-    };
-    let mut reached = 1 * serie_1_reach; // Start at center block
-    let mut k: usize = 1; // Skip center
-    while k * 2 < radius as usize {
-        reached += 2 * k * 4 * serie_1_reach;
-        k += 1;
-    }
-    let mut k: usize = 0;
-    while k * 2 + 1 < radius as usize {
-        reached += (2 * k + 1) * 4 * serie_2_reach;
-        k += 1;
+/// Sum of floor((a * i + b) / m) for i in 0..n.
+fn floor_sum(mut n: u64, mut m: u64, mut a: u64, mut b: u64) -> u64 {
+    let mut sum = 0;
+    loop {
+        if a >= m {
+            sum += n * (n - 1) / 2 * (a / m);
+            a %= m;
+        }
+        if b >= m {
+            sum += n * (b / m);
+            b %= m;
+        }
+        let y_max = a * n + b;
+        if y_max < m {
+            return sum;
+        }
+        (n, b) = (y_max / m, y_max % m);
+        std::mem::swap(&mut m, &mut a);
     }
+}
 
-    // Compute a, b, c, d blocks. There are "radius" times each.
-    let remaining = (field.start.0 - 1) as usize;
-    reached += (clipping_reach(&field, (side - 1, side - 1), remaining) // a
-                + clipping_reach(&field, (0, side - 1), remaining)      // b
-                + clipping_reach(&field, (0, 0), remaining)             // c
-                + clipping_reach(&field, (side - 1, 0), remaining))     // d
-        * radius as usize;
+const MAX_RADIUS: usize = 8;
+const UNREACHED: u32 = u32::MAX;
 
-    // Compute W, X, Y, Z blocks. There are "radius - 1" times each.
-    let remaining = (side + field.start.0 - 1) as usize;
-    reached += (clipping_reach(&field, (side - 1, side - 1), remaining) // X
-                + clipping_reach(&field, (0, side - 1), remaining)      // Z
-                + clipping_reach(&field, (0, 0), remaining)             // Y
-                + clipping_reach(&field, (side - 1, 0), remaining))     // W
-        * (radius - 1) as usize;
+/// Distances from `start` to every tile in a window of `fields` by `fields`
+/// copies of the field, with `start` in the middle one. Rows are `fields *
+/// field.width` tiles long.
+fn window_distances(field: &Field, start: _2D, fields: usize) -> Vec<u32> {
+    let (w, h) = (field.width, field.height);
+    let (gw, gh) = (fields * w, fields * h);
+    let rocks = (0..h)
+        .flat_map(|y| (0..w).map(move |x| (x as isize, y as isize)))
+        .map(|t| field.tiles[&t] == Tile::Rock)
+        .collect::<Vec<_>>();
+    let mut dist = vec![UNREACHED; gw * gh];
+    let first = (fields / 2 * h + start.1 as usize) * gw + fields / 2 * w + start.0 as usize;
+    dist[first] = 0;
+    let mut open = VecDeque::from([first]);
+    while let Some(i) = open.pop_front() {
+        let (x, y) = ((i % gw) as isize, (i / gw) as isize);
+        for (dx, dy) in [NORTH, SOUTH, EAST, WEST] {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= gw as isize || ny >= gh as isize {
+                continue;
+            }
+            let (nx, ny) = (nx as usize, ny as usize);
+            let next = ny * gw + nx;
+            if dist[next] != UNREACHED || rocks[ny % h * w + nx % w] {
+                continue;
+            }
+            dist[next] = dist[i] + 1;
+            open.push_back(next);
+        }
+    }
+    dist
+}
 
-    // Compute A, B, C, D blocks, they are all unique
-    let remaining = (side - 1) as usize;
-    reached += clipping_reach(&field, (field.start.0, side - 1), remaining) // A
-        + clipping_reach(&field, (field.start.0, 0), remaining)             // B
-        + clipping_reach(&field, (side - 1, field.start.1), remaining)      // C
-        + clipping_reach(&field, (0, field.start.1), remaining); // D
+fn infinite_reach(field: &Field, start: _2D, step_count: usize) -> Option<usize> {
+    // Compute distances from start to every copy of each tile, in a window of
+    // fields around the start's field. Far enough from start, distances grow
+    // by a constant stride from one field to the next along each axis (the
+    // field's width or height when there are straight lanes across it):
+    //
+    //   cXXXXXc     S: fields within the radius, counted one by one
+    //   Y.....Y     X, Y: fields on the edge of the radius, each of them is
+    //   Y..S..Y           repeated towards the outside along one axis
+    //   Y.....Y     c: fields in the corners, repeated in a whole quadrant
+    //   cXXXXXc
+    //
+    // The radius is doubled until this is verified on the layer of fields
+    // just outside of it. Then, tiles reached in repeated fields are counted
+    // in closed form: a tile at distance d in an edge field is reached in the
+    // k-th field further away if d + k * stride <= steps with the same parity
+    // as steps, and similarly in 2D for the corners. If rocks force detours
+    // across the field, distances in the corners do not follow this pattern
+    // and there is no solution.
+    let (w, h) = (field.width, field.height);
+    let steps = step_count as u64;
+    let tiles = || (0..h).flat_map(|y| (0..w).map(move |x| (x, y)));
+    let mut radius = 1;
+    while radius <= MAX_RADIUS {
+        // Besides the fields within the radius and the layer to check against,
+        // keep a margin so that paths to that layer are not cut by the window.
+        let fields = 2 * radius + 5;
+        let dist = window_distances(field, start, fields);
+        // Rocks may wall the start in: when nothing is reached in the outer
+        // layer of fields, nothing is reached further away either.
+        let (gw, gh) = (fields * w, fields * h);
+        let outer = |i: usize| {
+            let (x, y) = (i % gw, i / gw);
+            x < w || y < h || x >= gw - w || y >= gh - h
+        };
+        if (0..dist.len()).all(|i| dist[i] == UNREACHED || !outer(i)) {
+            let reached = dist.iter().filter(|d| {
+                **d != UNREACHED && (**d as u64) <= steps && (steps - **d as u64).is_multiple_of(2)
+            });
+            return Some(reached.count());
+        }
+        let r = radius as isize;
+        // Distance to tile (x, y) in field (fx, fy), relative to start's field
+        let at = |(fx, fy): _2D, (x, y): (usize, usize)| {
+            let gx = (fx + r + 2) as usize * w + x;
+            let gy = (fy + r + 2) as usize * h + y;
+            dist[gy * fields * w + gx]
+        };
+        // Fields on the edge of the radius towards `dir`, with the next field
+        let edge = |(dx, dy): _2D| {
+            (-r..=r).map(move |f| {
+                let field = (dx * r + dy.abs() * f, dy * r + dx.abs() * f);
+                (field, add(field, (dx, dy)))
+            })
+        };
+        // Distance between copies of a tile reached on both sides of an edge,
+        // if there is one and it grows.
+        let stride = |dir: _2D| {
+            edge(dir)
+                .flat_map(|fields| tiles().map(move |t| (fields, t)))
+                .find(|&((from, _), t)| at(from, t) != UNREACHED)
+                .and_then(|((from, to), t)| match at(to, t) {
+                    UNREACHED => None,
+                    d => d.checked_sub(at(from, t)).filter(|s| *s > 0),
+                })
+        };
+        let (Some(stride_x), Some(stride_y)) = (stride(EAST), stride(SOUTH)) else {
+            radius *= 2;
+            continue;
+        };
+        let linear = [
+            (EAST, stride_x),
+            (WEST, stride_x),
+            (SOUTH, stride_y),
+            (NORTH, stride_y),
+        ]
+        .into_iter()
+        .all(|(dir, stride)| {
+            edge(dir).all(|(from, to)| {
+                tiles().all(|t| match at(from, t) {
+                    UNREACHED => at(to, t) == UNREACHED,
+                    d => d.checked_add(stride) == Some(at(to, t)),
+                })
+            })
+        });
+        if !linear {
+            radius *= 2;
+            continue;
+        }
 
-    reached
+        let mut count = 0;
+        for fy in -r..=r {
+            for fx in -r..=r {
+                for t in tiles() {
+                    let d = at((fx, fy), t);
+                    if d == UNREACHED || d as u64 > steps {
+                        continue;
+                    }
+                    let d = d as u64;
+                    let t = steps - d;
+                    count += match (fx.abs() == r, fy.abs() == r) {
+                        (false, false) => u64::from(t % 2 == 0),
+                        (true, false) => ray_count(t, stride_x as u64),
+                        (false, true) => ray_count(t, stride_y as u64),
+                        (true, true) => quadrant_count(t, stride_x as u64, stride_y as u64),
+                    };
+                }
+            }
+        }
+        return Some(count as usize);
+    }
+    None
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
@@ -252,12 +352,7 @@ pub fn part_one(input: &str) -> Option<usize> {
 
 pub fn part_two(input: &str) -> Option<usize> {
     let field = parse(&input);
-    //let steps = 26501365;
-    let steps = field.width * 2 + field.width - 1 - field.start.0 as usize;
-    let reached = special_reach(&field, steps);
-    //let compare = generalized_reach(&field, field.start, steps, false);
-    //assert_eq!(reached, compare.len());
-    Some(reached)
+    infinite_reach(&field, field.start, 26501365)
 }
 
 #[cfg(test)]
@@ -273,6 +368,55 @@ mod tests {
     #[test]
     fn test_part_two() {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, Some(588));
+        assert_eq!(result, Some(528192865877841));
+    }
+
+    #[test]
+    fn test_infinite_reach() {
+        let field = parse(&advent_of_code::template::read_file("examples", DAY));
+        for steps in [1, 6, 10, 27, 50, 100, 201] {
+            let expected = generalized_reach(&field, field.start, steps, false).len();
+            assert_eq!(infinite_reach(&field, field.start, steps), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_infinite_reach_any_shape() {
+        // Rectangular field, start off-center
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let input = input.lines().skip(1).take(8).collect::<Vec<_>>().join("\n");
+        let field = parse(&input);
+        for start in [field.start, (0, 0), (10, 3)] {
+            if field.tiles[&start] == Tile::Rock {
+                continue;
+            }
+            for steps in [1, 7, 20, 33, 64] {
+                let expected = generalized_reach(&field, start, steps, false).len();
+                assert_eq!(infinite_reach(&field, start, steps), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_infinite_reach_walled_in() {
+        // Start and the tile across the field's edge are walled in together,
+        // on either axis
+        for input in [
+            ".....\n##..#\n..##S\n##..#\n.....\n",
+            ".#S#.\n..#..\n.....\n..#..\n.#.#.\n",
+        ] {
+            let field = parse(input);
+            for steps in [1, 2, 7, 26501365] {
+                let small = steps.min(10 + steps % 2);
+                let expected = generalized_reach(&field, field.start, small, false).len();
+                assert_eq!(infinite_reach(&field, field.start, steps), Some(expected));
+            }
+        }
+        // Only part of the field is walled in, the start is not
+        let field = parse("S....\n#...#\n.#.#.\n#...#\n.....\n");
+        for steps in [1, 6, 11, 30] {
+            let expected = generalized_reach(&field, field.start, steps, false).len();
+            assert_eq!(infinite_reach(&field, field.start, steps), Some(expected));
+        }
     }
 }
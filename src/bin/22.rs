@@ -1,5 +1,3 @@
-use std::collections::VecDeque;
-
 advent_of_code::solution!(22);
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -17,7 +15,7 @@ struct Brick {
 type Bricks = Vec<Brick>;
 
 fn parse(input: &str) -> Bricks {
-    let re = regex::Regex::new(r"(?m)^(\d+),(\d+),(\d+)~(\d+),(\d+),(\d+)$").unwrap();
+    let re = advent_of_code::regex!(r"(?m)^(\d+),(\d+),(\d+)~(\d+),(\d+),(\d+)$");
     re.captures_iter(input)
         .map(|cap| {
             let mut brick = Brick {
@@ -50,132 +48,145 @@ fn parse(input: &str) -> Bricks {
         .collect()
 }
 
-fn collide(a: &Brick, b: &Brick) -> bool {
-    (a.beg.x <= b.end.x && a.end.x >= b.beg.x) && (a.beg.y <= b.end.y && a.end.y >= b.beg.y)
-}
-
-fn snap(a: &Brick, z: isize) -> Brick {
+fn drop_to(a: &Brick, z: isize) -> Brick {
     Brick {
-        beg: _3D {
-            x: a.beg.x,
-            y: a.beg.y,
-            z,
-        },
+        beg: _3D { z, ..a.beg },
         end: _3D {
-            x: a.end.x,
-            y: a.end.y,
             z: a.end.z - a.beg.z + z,
+            ..a.end
         },
     }
 }
 
-fn at_rest(bricks: &Bricks) -> Bricks {
-    let mut falling = bricks.clone();
-    falling.sort_by_key(|b| b.beg.z);
-    // Snap all bricks to their lowest z-order, scan in front.
-    for i in 0..falling.len() {
-        let brick = &falling[i];
-        let mut z = 1;
-        for j in 0..i {
-            let test = falling[i - j - 1];
-            if test.end.z >= brick.beg.z {
-                continue; // test already at same or higher level
-            }
-            if collide(&brick, &test) {
-                z = std::cmp::max(z, test.end.z + 1);
-            }
-        }
-        falling[i] = snap(&brick, z);
-    }
-    falling
-}
-
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct Edges {
     supporting: Vec<usize>,
     supported_by: Vec<usize>,
 }
-type Graph = std::collections::HashMap<usize, Edges>;
+type Graph = Vec<Edges>;
+
+/// Bricks at rest, in input order, with the graph of which brick rests on
+/// which.
+#[derive(Debug)]
+struct Pile {
+    bricks: Bricks,
+    graph: Graph,
+    order: Vec<usize>, // bottom-up: a brick comes after all its supports
+}
 
-fn supporting(ordered_bricks: &Bricks) -> Graph {
-    let mut graph = Graph::new();
-    for i in 0..ordered_bricks.len() {
-        let brick = &ordered_bricks[i];
-        graph.entry(i).or_default();
-        for j in i + 1..ordered_bricks.len() {
-            let test = &ordered_bricks[j];
-            if test.beg.z != brick.end.z + 1 {
-                continue; // not interested
-            }
-            if test.beg.z > brick.end.z + 1 {
-                break; // no more collision possible
+impl Pile {
+    fn settle(falling: &Bricks) -> Self {
+        // Drop bricks from the lowest one up, keeping a height map of the top
+        // brick in each x/y column: a brick stops just above the highest top
+        // under it, and rests on each brick having its top at that height.
+        let mut order = (0..falling.len()).collect::<Vec<_>>();
+        order.sort_by_key(|i| falling[*i].beg.z);
+        let width = falling.iter().map(|b| b.end.x + 1).max().unwrap_or(0) as usize;
+        let depth = falling.iter().map(|b| b.end.y + 1).max().unwrap_or(0) as usize;
+        let mut top: Vec<Option<(isize, usize)>> = vec![None; width * depth];
+        let mut bricks = falling.clone();
+        let mut graph = vec![Edges::default(); falling.len()];
+        for &i in order.iter() {
+            let brick = &falling[i];
+            let columns = (brick.beg.y..=brick.end.y)
+                .flat_map(|y| {
+                    (brick.beg.x..=brick.end.x).map(move |x| y as usize * width + x as usize)
+                })
+                .collect::<Vec<_>>();
+            let floor = columns
+                .iter()
+                .filter_map(|c| top[*c].map(|(z, _)| z))
+                .max()
+                .unwrap_or(0);
+            let mut supports = columns
+                .iter()
+                .filter_map(|c| top[*c].filter(|(z, _)| *z == floor).map(|(_, j)| j))
+                .collect::<Vec<_>>();
+            supports.sort();
+            supports.dedup();
+            for &j in supports.iter() {
+                graph[j].supporting.push(i);
             }
-            if collide(&brick, &test) {
-                graph.entry(i).and_modify(|v| v.supporting.push(j));
-                graph.entry(j).or_default().supported_by.push(i);
+            graph[i].supported_by = supports;
+            bricks[i] = drop_to(brick, floor + 1);
+            for c in columns {
+                top[c] = Some((bricks[i].end.z, i));
             }
         }
+        Self {
+            bricks,
+            graph,
+            order,
+        }
     }
-    assert_eq!(ordered_bricks.len(), graph.len());
-    graph
-}
-
-fn can_desintegrate(support_graph: &Graph) -> usize {
-    support_graph
-        .iter()
-        .filter(|(_, edges)| {
-            edges.supporting.len() == 0
-            ||
-            // Can be desintegrated if the supported bricks have all more than 1 support
-            edges.supporting.iter().all(|brick| support_graph[&brick].supported_by.len() > 1)
-        })
-        .count()
-}
 
-fn chain_desintegration(support_graph: &Graph) -> usize {
-    support_graph
-        .iter()
-        .map(|(this_brick, its_edges)| {
-            // Breadth-first search of bricks to fall
-            let mut open = its_edges
-                .supporting
-                .iter()
-                .copied()
-                .collect::<VecDeque<_>>();
-            let mut falling = std::collections::HashSet::from([*this_brick]);
-            while let Some(brick) = open.pop_front() {
-                if falling.contains(&brick) {
-                    continue;
-                }
-                let edges = &support_graph[&brick];
-                if edges.supported_by.iter().any(|b| !falling.contains(b)) {
-                    continue;
+    /// For each brick, the number of other bricks that fall if it is removed.
+    fn falling_counts(&self) -> Vec<usize> {
+        // A brick falls when another one is removed iff that other brick is on
+        // every path from the ground to it in the support graph, i.e. it
+        // dominates it. Bottom-up, the immediate dominator of a brick is the
+        // nearest common dominator of its supports (the ground is the root),
+        // and the bricks that fall with a removed brick are its subtree in the
+        // dominator tree.
+        let ground = self.bricks.len();
+        let mut idom = vec![ground; ground + 1];
+        let mut level = vec![0; ground + 1];
+        for &i in self.order.iter() {
+            let mut supports = self.graph[i].supported_by.iter().copied();
+            let Some(first) = supports.next() else {
+                level[i] = 1; // on the ground
+                continue;
+            };
+            let dom = supports.fold(first, |mut a, mut b| {
+                while a != b {
+                    if level[a] >= level[b] {
+                        a = idom[a];
+                    } else {
+                        b = idom[b];
+                    }
                 }
-                open.extend(&edges.supporting);
-                falling.insert(brick);
+                a
+            });
+            idom[i] = dom;
+            level[i] = level[dom] + 1;
+        }
+        // Subtree sizes, top-down so that children are done before parents
+        let mut size = vec![1; ground + 1];
+        for &i in self.order.iter().rev() {
+            size[idom[i]] += size[i];
+        }
+        size.truncate(ground);
+        size.iter().map(|s| s - 1).collect()
+    }
+
+    /// Bricks (other than the removed ones) that fall if all bricks in
+    /// `removed` are removed at once.
+    #[allow(dead_code)]
+    fn falling_if_removed(&self, removed: &[usize]) -> Vec<usize> {
+        let mut gone = vec![false; self.bricks.len()];
+        for &i in removed {
+            gone[i] = true;
+        }
+        let mut falling = vec![];
+        for &i in self.order.iter() {
+            let supports = &self.graph[i].supported_by;
+            if !gone[i] && !supports.is_empty() && supports.iter().all(|j| gone[*j]) {
+                gone[i] = true;
+                falling.push(i);
             }
-            //println!("Brick {} causes {} to fall", this_brick, falling.len());
-            falling.len() - 1 // We count the *other* bricks only
-        })
-        .sum()
+        }
+        falling
+    }
 }
 
 pub fn part_one(input: &str) -> Option<usize> {
-    let bricks = parse(&input);
-    let ordered_bricks = at_rest(&bricks);
-    //println!("{:?}", ordered_bricks);
-    let support_graph = supporting(&ordered_bricks);
-    //println!("{:?}", support_graph);
-    Some(can_desintegrate(&support_graph))
+    let pile = Pile::settle(&parse(input));
+    Some(pile.falling_counts().iter().filter(|n| **n == 0).count())
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
-    let bricks = parse(&input);
-    let ordered_bricks = at_rest(&bricks);
-    //println!("{:?}", ordered_bricks);
-    let support_graph = supporting(&ordered_bricks);
-    //println!("{:?}", support_graph);
-    Some(chain_desintegration(&support_graph))
+    let pile = Pile::settle(&parse(input));
+    Some(pile.falling_counts().iter().sum())
 }
 
 #[cfg(test)]
@@ -193,4 +204,17 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(8));
     }
+
+    #[test]
+    fn test_falling_if_removed() {
+        let pile = Pile::settle(&parse(&advent_of_code::template::read_file(
+            "examples", DAY,
+        )));
+        let counts = pile.falling_counts();
+        for (i, count) in counts.iter().enumerate() {
+            assert_eq!(pile.falling_if_removed(&[i]).len(), *count);
+        }
+        assert_eq!(pile.falling_if_removed(&[1, 2]), vec![3, 4, 5, 6]);
+        assert_eq!(pile.falling_if_removed(&[7, 8]), vec![10]);
+    }
 }
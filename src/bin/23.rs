@@ -1,5 +1,7 @@
 advent_of_code::solution!(23);
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 #[derive(Debug, Eq, Hash, PartialEq, Copy, Clone, Default)]
 struct _2D {
     x: isize,
@@ -184,60 +186,203 @@ fn graph_without_slopes(terrain: &Terrain) -> Graph {
     simplify(graph)
 }
 
-#[derive(Debug, Eq, Copy, Clone)]
-struct State(usize, _2D, Option<usize>);
-
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
-    }
+/// The trail graph with vertices indexed densely, so that a set of visited
+/// vertices fits in a bitmask. Edges are `(to, steps)`.
+#[derive(Debug)]
+struct Trails {
+    out: Vec<Vec<(usize, usize)>>,
+    start: usize,
+    end: usize,
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl Trails {
+    fn new(graph: &Graph, start: _2D, end: _2D) -> Self {
+        let mut tiles = graph.keys().copied().collect::<Vec<_>>();
+        tiles.sort_by_key(|t| (t.y, t.x));
+        let id = |t: &_2D| tiles.iter().position(|u| u == t).unwrap();
+        let mut out = tiles
+            .iter()
+            .map(|t| {
+                let vertex = &graph[t];
+                let edges = vertex.out.iter();
+                edges
+                    .map(|e| (id(&e.to), vertex.weight + e.weight))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        let (start, end) = (id(&start), id(&end));
+        contract(&mut out, start, end);
+        // Keep only vertices reachable from start, renumbered in order
+        let mut keep = vec![false; out.len()];
+        let mut open = vec![start];
+        while let Some(v) = open.pop() {
+            if !std::mem::replace(&mut keep[v], true) {
+                open.extend(out[v].iter().map(|(to, _)| *to));
+            }
+        }
+        let mut ids = vec![usize::MAX; out.len()];
+        let mut next = 0;
+        for v in (0..out.len()).filter(|v| keep[*v]) {
+            ids[v] = next;
+            next += 1;
+        }
+        assert!(next <= 64, "{next} vertices don't fit in a bitmask");
+        let out = (0..out.len())
+            .filter(|v| keep[*v])
+            .map(|v| out[v].iter().map(|(to, w)| (ids[*to], *w)).collect())
+            .collect();
+        Self {
+            out,
+            start: ids[start],
+            end: ids[end],
+        }
     }
-}
 
-impl PartialEq for State {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+    /// Length of the longest route from start to end that visits each
+    /// vertex at most once, searching with `threads` threads.
+    fn longest_route(&self, threads: usize) -> Option<usize> {
+        // Depth-first search with visited vertices in a bitmask. A route can't
+        // get longer than the longest edge out of each vertex it can still
+        // visit, which is an upper bound to prune branches that won't beat
+        // the best route so far. To share the work, the top of the search
+        // tree is expanded breadth-first into many routes, which threads pick
+        // from one after the other, sharing the best route as they go.
+        let longest_out = self
+            .out
+            .iter()
+            .enumerate()
+            .map(|(v, edges)| match v == self.end {
+                true => 0,
+                false => edges.iter().map(|(_, w)| *w).max().unwrap_or(0),
+            })
+            .collect::<Vec<_>>();
+        let mut routes = vec![Route {
+            at: self.start,
+            visited: 1 << self.start,
+            length: 0,
+            bound: longest_out.iter().sum(),
+        }];
+        let best = AtomicUsize::new(0); // Any route to end takes some steps
+        while threads > 1 && routes.len() < threads * 16 {
+            let mut next = vec![];
+            for route in routes.iter() {
+                if route.at == self.end {
+                    best.fetch_max(route.length, Ordering::Relaxed);
+                } else {
+                    next.extend(self.next_routes(route, &longest_out));
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            routes = next;
+        }
+        let picked = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    while let Some(route) = routes.get(picked.fetch_add(1, Ordering::Relaxed)) {
+                        self.search(*route, &longest_out, &best);
+                    }
+                });
+            }
+        });
+        Some(best.into_inner()).filter(|best| *best > 0)
     }
-}
 
-type Histories = Vec<(_2D, Option<usize>)>;
+    fn next_routes<'a>(
+        &'a self,
+        route: &'a Route,
+        longest_out: &'a [usize],
+    ) -> impl Iterator<Item = Route> + 'a {
+        self.out[route.at]
+            .iter()
+            .filter(|(to, _)| route.visited & 1 << to == 0)
+            .map(|(to, w)| Route {
+                at: *to,
+                visited: route.visited | 1 << to,
+                length: route.length + w,
+                bound: route.bound - longest_out[route.at],
+            })
+    }
 
-fn find_history(histories: &Histories, mut head: usize, tile: _2D) -> bool {
-    // histories are a DAG, we explore ancesters from head to find tile
-    while histories[head].1 != None {
-        if histories[head].0 == tile {
-            return true;
+    fn search(&self, route: Route, longest_out: &[usize], best: &AtomicUsize) {
+        if route.at == self.end {
+            best.fetch_max(route.length, Ordering::Relaxed);
+            return;
+        }
+        if route.length + route.bound <= best.load(Ordering::Relaxed) {
+            return;
+        }
+        for next in self.next_routes(&route, longest_out) {
+            self.search(next, longest_out, best);
         }
-        head = histories[head].1.unwrap();
     }
-    histories[head].0 == tile
 }
 
-fn longest_route(graph: &Graph, start: _2D, end: _2D) -> Option<usize> {
-    // Dikjstra but reverse heuristic, with individual path storage
-    let mut open = std::collections::BinaryHeap::from([State(0, start, None)]);
-    let mut histories = Histories::new();
-    let mut best: Option<State> = None;
-    while let Some(State(dist, tile, parent)) = open.pop() {
-        if tile == end && (best.is_none() || best.unwrap().0 < dist) {
-            best = Some(State(dist, tile, parent));
-        }
-        if parent.is_some() && find_history(&histories, parent.unwrap(), tile) {
-            continue;
+/// A route being searched. `bound` is the sum of the longest edge out of each
+/// vertex not visited yet, or the current one.
+#[derive(Debug, Clone, Copy)]
+struct Route {
+    at: usize,
+    visited: u64,
+    length: usize,
+    bound: usize,
+}
+
+fn contract(out: &mut [Vec<(usize, usize)>], start: usize, end: usize) {
+    // Further reduce the graph, until nothing changes:
+    //
+    //  - A vertex with a single way out is replaced by edges from each of
+    //    its predecessors to its successor (with the weights added up), since
+    //    a route going there has no other choice. For slopes, this removes
+    //    all the single tiles left between junctions.
+    //  - Dead-ends are removed, as well as edges back to the vertex itself.
+    //  - If a single vertex leads to end, a route that leaves it towards any
+    //    other vertex can't reach end anymore, so only the edge to end stays.
+    //  - Parallel edges keep only the longest one.
+    let mut removed = vec![false; out.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let to_end = (0..out.len())
+            .filter(|u| !removed[*u] && out[*u].iter().any(|(to, _)| *to == end))
+            .collect::<Vec<_>>();
+        if let [u] = to_end[..] {
+            if u != start && out[u].len() > 1 {
+                out[u].retain(|(to, _)| *to == end);
+                changed = true;
+            }
         }
-        histories.push((tile, parent));
-        let parent = Some(histories.len() - 1);
-        let dist = dist + graph[&tile].weight;
-        for edge in &graph[&tile].out {
-            open.push(State(dist + edge.weight, edge.to, parent));
+        for v in 0..out.len() {
+            if removed[v] || v == start || v == end {
+                continue;
+            }
+            out[v].retain(|(to, _)| *to != v);
+            let successor = match out[v][..] {
+                [] => None,
+                [(to, w)] => Some((to, w)),
+                _ => continue,
+            };
+            removed[v] = true;
+            changed = true;
+            for (u, edges) in out.iter_mut().enumerate() {
+                *edges = std::mem::take(edges)
+                    .into_iter()
+                    .filter_map(|(to, w)| match (to == v, successor) {
+                        (false, _) => Some((to, w)),
+                        (true, Some((to, sw))) => Some((to, w + sw)).filter(|_| to != u),
+                        (true, None) => None,
+                    })
+                    .collect();
+                edges.sort_by_key(|(to, w)| (*to, std::cmp::Reverse(*w)));
+                edges.dedup_by_key(|(to, _)| *to);
+            }
         }
     }
-    best.and_then(|s| Some(s.0))
+    for v in (0..out.len()).filter(|v| removed[*v]) {
+        out[v].clear();
+    }
 }
 
 fn start(graph: &Graph) -> _2D {
@@ -261,7 +406,7 @@ pub fn part_one(input: &str) -> Option<usize> {
     //println!("{:?}", terrain);
     let graph = graph_with_slopes(&terrain);
     //println!("{:?}", graph);
-    longest_route(&graph, start(&graph), goal(&graph))
+    Trails::new(&graph, start(&graph), goal(&graph)).longest_route(1)
 }
 
 pub fn part_two(input: &str) -> Option<usize> {
@@ -269,7 +414,8 @@ pub fn part_two(input: &str) -> Option<usize> {
     //println!("{:?}", terrain);
     let graph = graph_without_slopes(&terrain);
     //println!("{:?}", graph);
-    longest_route(&graph, start(&graph), goal(&graph))
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Trails::new(&graph, start(&graph), goal(&graph)).longest_route(threads)
 }
 
 #[cfg(test)]
@@ -287,4 +433,14 @@ mod tests {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
         assert_eq!(result, Some(154));
     }
+
+    #[test]
+    fn test_longest_route_threads() {
+        let terrain = parse(&advent_of_code::template::read_file("examples", DAY));
+        let graph = graph_without_slopes(&terrain);
+        let trails = Trails::new(&graph, start(&graph), goal(&graph));
+        for threads in [1, 3, 8] {
+            assert_eq!(trails.longest_route(threads), Some(154));
+        }
+    }
 }
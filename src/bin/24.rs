@@ -1,5 +1,8 @@
 advent_of_code::solution!(24);
 
+use num::rational::Ratio;
+use num::{BigInt, BigRational, ToPrimitive, Zero};
+
 #[derive(Debug, Copy, Clone)]
 struct _3D {
     x: i64,
@@ -7,11 +10,15 @@ struct _3D {
     z: i64,
 }
 
-#[derive(Debug, Copy, Clone)]
-struct _3Df {
-    x: f64,
-    y: f64,
-    _z: f64,
+/// Exact rational, hailstones' coordinates are ~4e14 so products stay far
+/// from overflowing i128.
+type Q = Ratio<i128>;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct _3Dq {
+    x: Q,
+    y: Q,
+    z: Q,
 }
 
 impl std::ops::Add for _3D {
@@ -25,6 +32,17 @@ impl std::ops::Add for _3D {
     }
 }
 
+impl std::ops::Sub for _3D {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 struct Hailstone {
     pos: _3D,
@@ -32,9 +50,9 @@ struct Hailstone {
 }
 
 fn parse(input: &str) -> Vec<Hailstone> {
-    let re =
-        regex::Regex::new(r"(?m)^(-?\d+),\s+(-?\d+),\s+(-?\d+) @\s+(-?\d+),\s+(-?\d+),\s+(-?\d+)$")
-            .unwrap();
+    let re = advent_of_code::regex!(
+        r"(?m)^(-?\d+),\s+(-?\d+),\s+(-?\d+) @\s+(-?\d+),\s+(-?\d+),\s+(-?\d+)$"
+    );
     re.captures_iter(input)
         .map(|cap| Hailstone {
             pos: _3D {
@@ -51,98 +69,46 @@ fn parse(input: &str) -> Vec<Hailstone> {
         .collect::<Vec<_>>()
 }
 
-enum Trajectory {
-    Parallel,
-    Intersect,
-}
-
-fn xy_intersect(a: &Hailstone, b: &Hailstone) -> (Trajectory, Option<(_3Df, f64, f64)>) {
+fn xy_intersect(a: &Hailstone, b: &Hailstone) -> Option<((Q, Q), Q, Q)> {
     // To find intersection (if any) between two hailstones (but not necessarily
-    // collision), we only need afine equations. So convert parametric
-    // equations to afine equations by eliminating t:
-    //
-    //   (1)         a.pos.x + a.vel.x * t == x  ->  t == (x - a.pos.x) / a.vel.x
-    //   (2)         a.pos.y + a.vel.y * t == y  ->  t == (y - a.pos.y) / a.vel.y
-    //   (1) == (2)  (x - a.pos.x) / a.vel.x == (y - a.pos.y) / a.vel.y
-    //   (1) == (2)  y == a.vel.y * (x - a.pos.x) / a.vel.x + a.pos.y
-    //   (1) == (2)  y == a.vel.y / a.vel.x * x - a.vel.y * a.pos.x / a.vel.x + a.pos.y
+    // collision), solve for ta and tb in x and y:
     //
-    // That's for a, but we can do the same for b. We now have two equations.
-    // Solve them:
+    //   (1)  a.pos.x + a.vel.x * ta == b.pos.x + b.vel.x * tb
+    //   (2)  a.pos.y + a.vel.y * ta == b.pos.y + b.vel.y * tb
     //
-    //   (3)          y == a.vel.y / a.vel.x * x - a.vel.y * a.pos.x / a.vel.x + a.pos.y
-    //   (4)          y == b.vel.y / b.vel.x * x - b.vel.y * b.pos.x / b.vel.x + b.pos.y
-    //   (3) == (4)   a.vel.y / a.vel.x * x - a.vel.y * a.pos.x / a.vel.x + a.pos.y
-    //                == b.vel.y / b.vel.x * x - b.vel.y * b.pos.x / b.vel.x + b.pos.y
-    //   (3) == (4)   0 == a.vel.y / a.vel.x * x - b.vel.y / b.vel.x * x
-    //                - a.vel.y * a.pos.x / a.vel.x + a.pos.y + b.vel.y * b.pos.x / b.vel.x - b.pos.y
-    //   (3) == (4)   0 == (a.vel.y / a.vel.x - b.vel.y / b.vel.x) * x
-    //                + b.vel.y * b.pos.x / b.vel.x - a.vel.y * a.pos.x / a.vel.x + a.pos.y - b.pos.y
-    //   (3) == (4)   x == (a.vel.y * a.pos.x / a.vel.x - b.vel.y * b.pos.x / b.vel.x + b.pos.y - a.pos.y)
-    //                / (a.vel.y / a.vel.x - b.vel.y / b.vel.x)
+    // With d = b.pos - a.pos and det = a.vel.x * b.vel.y - a.vel.y * b.vel.x,
+    // Cramer's rule gives:
     //
-    // Once we get x, plug it back into (3) or (4) to get y.
+    //   ta == (d.x * b.vel.y - d.y * b.vel.x) / det
+    //   tb == (d.x * a.vel.y - d.y * a.vel.x) / det
     //
-    // Compute with integers when possible, give approx floating point answers
-    // for return value. Hoping for no overflow/underflow
-    assert_ne!(a.vel.x, 0);
-    assert_ne!(a.vel.y, 0);
-    if a.vel.y * b.vel.x == b.vel.y * a.vel.x {
-        // ra == rb ?
-        return (Trajectory::Parallel, None);
+    // There is no intersection when det == 0, the paths are parallel.
+    // Everything is kept as exact rationals so that points landing on the
+    // border of the test area are classified correctly.
+    let det = a.vel.x as i128 * b.vel.y as i128 - a.vel.y as i128 * b.vel.x as i128;
+    if det == 0 {
+        return None;
     }
-    let ra = a.vel.y as f64 / a.vel.x as f64;
-    let rb = b.vel.y as f64 / b.vel.x as f64;
-    let x = (ra * a.pos.x as f64 - rb * b.pos.x as f64 + (b.pos.y - a.pos.y) as f64) / (ra - rb);
-    let y = ra * x + a.pos.y as f64 - ra * a.pos.x as f64;
-    let z = f64::default(); // x, y only
-    let ta = (x - a.pos.x as f64) / a.vel.x as f64;
-    let tb = (x - b.pos.x as f64) / b.vel.x as f64;
-    return (Trajectory::Intersect, Some((_3Df { x, y, _z: z }, ta, tb)));
+    let d = b.pos - a.pos;
+    let (dx, dy) = (d.x as i128, d.y as i128);
+    let ta = Q::new(dx * b.vel.y as i128 - dy * b.vel.x as i128, det);
+    let tb = Q::new(dx * a.vel.y as i128 - dy * a.vel.x as i128, det);
+    let x = Q::from(a.pos.x as i128) + ta * a.vel.x as i128;
+    let y = Q::from(a.pos.y as i128) + ta * a.vel.y as i128;
+    Some(((x, y), ta, tb))
 }
 
-fn xy_intersections_area(hail: &Vec<Hailstone>, min: i64, max: i64) -> usize {
+fn xy_intersections_area(hail: &[Hailstone], min: i64, max: i64) -> usize {
+    let (min, max) = (Q::from(min as i128), Q::from(max as i128));
     let mut count = 0;
     for i in 0..hail.len() - 1 {
         for j in i + 1..hail.len() {
-            let a = hail[i];
-            let b = hail[j];
-            // println!();
-            // println!("Hailstone A: {:?}", a);
-            // println!("Hailstone B: {:?}", b);
-            let (trajectory, intersection) = xy_intersect(&&a, &&b);
-            match trajectory {
-                Trajectory::Intersect => {
-                    let (point, ta, tb) = intersection.unwrap();
-                    let min = min as f64;
-                    let max = max as f64;
-                    if ta < 0.0 || tb < 0.0 {
-                        // if ta < 0.0 && tb < 0.0 {
-                        //     println!(
-                        //         "Hailstones' paths intersected in the past for both hailstones"
-                        //     );
-                        // } else if ta < 0.0 {
-                        //     println!("Hailstones' paths intersected in the past for hailstone A");
-                        // } else {
-                        //     println!("Hailstones' paths intersected in the past for hailstone B");
-                        // }
-                        continue;
-                    }
-                    if min <= point.x && point.x <= max && min <= point.y && point.y <= max {
-                        // println!(
-                        //     "Hailstones' paths cross inside the test area at {:?}",
-                        //     point
-                        // );
-                        count += 1;
-                    } else {
-                        // println!(
-                        //     "Hailstones' paths cross outside the test area at {:?}",
-                        //     point
-                        // );
-                    }
+            if let Some(((x, y), ta, tb)) = xy_intersect(&hail[i], &hail[j]) {
+                if ta < Q::zero() || tb < Q::zero() {
+                    continue; // Paths intersected in the past
                 }
-                Trajectory::Parallel => {
-                    // println!("Hailstones' paths are parallel");
+                if min <= x && x <= max && min <= y && y <= max {
+                    count += 1;
                 }
             }
         }
@@ -150,6 +116,53 @@ fn xy_intersections_area(hail: &Vec<Hailstone>, min: i64, max: i64) -> usize {
     count
 }
 
+/// Where the paths of two hailstones cross, kept for debugging rock throws
+/// (see the tests): the rock's path crosses each hailstone's at the same time.
+#[allow(dead_code)]
+fn path_crossing(a: &Hailstone, b: &Hailstone) -> Option<(_3Dq, Q, Q)> {
+    // Same as `xy_intersect` in 3D, returns where paths cross as well as ta
+    // and tb, the times at which each hailstone is there. With
+    // n = a.vel x b.vel, paths cross if they are not parallel (n != 0) and are
+    // coplanar (d . n == 0). Then:
+    //
+    //   ta == ((d x b.vel) . n) / (n . n)
+    //   tb == ((d x a.vel) . n) / (n . n)
+    let n = cross(a.vel, b.vel);
+    let d = b.pos - a.pos;
+    let dot = |u: [i128; 3], v: [i128; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+    let nn = dot(n, n);
+    if nn == 0 || dot([d.x as i128, d.y as i128, d.z as i128], n) != 0 {
+        return None;
+    }
+    let ta = Q::new(dot(cross(d, b.vel), n), nn);
+    let tb = Q::new(dot(cross(d, a.vel), n), nn);
+    Some((position(a, ta), ta, tb))
+}
+
+fn position(h: &Hailstone, t: Q) -> _3Dq {
+    _3Dq {
+        x: Q::from(h.pos.x as i128) + t * h.vel.x as i128,
+        y: Q::from(h.pos.y as i128) + t * h.vel.y as i128,
+        z: Q::from(h.pos.z as i128) + t * h.vel.z as i128,
+    }
+}
+
+fn collision(a: &Hailstone, b: &Hailstone) -> Option<(Q, _3Dq)> {
+    // Hailstones collide at t if (a.pos - b.pos) + (a.vel - b.vel) * t == 0.
+    // When they share position and velocity, they collide from t == 0.
+    let dp = a.pos - b.pos;
+    let dv = a.vel - b.vel;
+    let dp = [dp.x as i128, dp.y as i128, dp.z as i128];
+    let dv = [dv.x as i128, dv.y as i128, dv.z as i128];
+    let t = match (0..3).find(|k| dv[*k] != 0) {
+        Some(k) => Q::new(-dp[k], dv[k]),
+        None => Q::zero(),
+    };
+    (0..3)
+        .all(|k| Q::from(dp[k]) + t * dv[k] == Q::zero())
+        .then(|| (t, position(a, t)))
+}
+
 pub fn part_one(input: &str) -> Option<usize> {
     let hail = parse(input);
     // test
@@ -162,60 +175,135 @@ pub fn part_one(input: &str) -> Option<usize> {
     //))
 }
 
-pub fn part_two(_input: &str) -> Option<usize> {
-    // Perfect collision means there must be a t where all x, y, z, are equal
-    // for any hailstones "a" we pick:
-    //
-    //    (1)         a.pos.x + a.vel.x * t = Px + Vx * t
-    //    (2)         a.pos.y + a.vel.y * t = Py + Vy * t
-    //    (3)         a.pos.z + a.vel.z * t = Pz + Vz * t
-    //
-    // We have these equations with any hailstones:
-    //
-    //    (1)         a.pos.x + a.vel.x * t1 = Px + Vx * t1
-    //    (4)         b.pos.x + b.vel.x * t2 = Px + Vx * t2
-    //    (5)         c.pos.x + c.vel.x * t3 = Px + Vx * t3
-    //                ...
-    //
-    // Variables t0, t1, ... are inconvenient; we could eliminate them by picking
-    // just another dimension such as:
-    //
-    //   (1 & 2)      (a.pos.x - Px) / (Vx - a.vel.x) = (a.pos.y - Py) / (Vy - a.vel.y)
-    //
-    // Now rearrange to remove nul divisors, do this for 4 hailstones, since we
-    // have 4 variables to solve for:
-    //
-    //   (6)        0 = (a.pos.x - Px) * (Vy - a.vel.y) - (a.pos.y - Py) * (Vx - a.vel.x)
-    //   (7)        0 = (b.pos.x - Px) * (Vy - b.vel.y) - (b.pos.y - Py) * (Vx - b.vel.x)
-    //   (8)        0 = (c.pos.x - Px) * (Vy - c.vel.y) - (c.pos.y - Py) * (Vx - c.vel.x)
-    //   (9)        0 = (d.pos.x - Px) * (Vy - d.vel.y) - (d.pos.y - Py) * (Vx - d.vel.x)
-    //                ...
-    //
-    // This is where I stopped and plugged all the values in Wolfram Alpha to
-    // make sure I'm on the right and haven't made mistakes. It spat out the
-    // integral answer:
-    //
-    //              Px = 129723668686742 ∧ Py = 353939130278484
-    //              ∧ Vx = 312 ∧ Vy = -116
-    //
-    // From there it was an easy path to "victory", picking 2 hailstones "a" and
-    // "b":
+fn cross(a: _3D, b: _3D) -> [i128; 3] {
+    let (ax, ay, az) = (a.x as i128, a.y as i128, a.z as i128);
+    let (bx, by, bz) = (b.x as i128, b.y as i128, b.z as i128);
+    [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx]
+}
+
+fn pair_equations(a: &Hailstone, b: &Hailstone) -> Vec<Vec<BigRational>> {
+    // The rock (P, V) hits hailstone "a" at some time t, so (a.pos - P) and
+    // (a.vel - V) are colinear:
     //
-    //              t(a) = 379155208275
-    //              t(b) = 495926101181
-    //              Pz = 227368817349775  ∧ Vz = 109
+    //   (1)        (a.pos - P) x (a.vel - V) = 0
+    //   (1)        a.pos x a.vel - a.pos x V - P x a.vel + P x V = 0
     //
-    // To find the real solution, I would need to re-arrange all the terms of
-    // the equation, while taking care to not leave any fractions since we're
-    // interested in integers. Then find 2 t's and get to Pz, Vz as I did above.
+    // The only non-linear term, P x V, is the same for all hailstones, so we
+    // eliminate it by subtracting the same equation for hailstone "b":
     //
-    // This is the way I know how to resolve this, but it's tedious. I'll be
-    // looking to see how others did it. My method works, but it requires an
-    // equation solver like the amazing WolframAlpha
-    // https://www.wolframalpha.com/
+    //   (1) - (2)  (a.vel - b.vel) x P - (a.pos - b.pos) x V
+    //                  = b.pos x b.vel - a.pos x a.vel
     //
-    // So I'm gonna leave it at that for now, and maybe I'll come back to it.
-    None
+    // That's 3 linear equations in 6 unknowns (Px, Py, Pz, Vx, Vy, Vz). Each
+    // row below is [coefficients of P, coefficients of V, constant].
+    let dv = a.vel - b.vel;
+    let dp = a.pos - b.pos;
+    let ca = cross(a.pos, a.vel);
+    let cb = cross(b.pos, b.vel);
+    // Rows of the matrix [u]x, such that u x X == [u]x * X
+    let skew = |u: _3D| [[0, -u.z, u.y], [u.z, 0, -u.x], [-u.y, u.x, 0]];
+    let (mv, mp) = (skew(dv), skew(dp));
+    (0..3)
+        .map(|k| {
+            mv[k]
+                .iter()
+                .map(|c| BigInt::from(*c))
+                .chain(mp[k].iter().map(|c| BigInt::from(-*c)))
+                .chain(std::iter::once(BigInt::from(cb[k] - ca[k])))
+                .map(BigRational::from_integer)
+                .collect()
+        })
+        .collect()
+}
+
+/// Outcome of solving a linear system.
+enum System {
+    Solved(Vec<BigRational>),
+    Underdetermined,
+    Inconsistent,
+}
+
+fn gaussian_elimination(mut rows: Vec<Vec<BigRational>>) -> System {
+    // Solves an augmented system, which may have more equations than unknowns.
+    let Some(unknowns) = rows.first().map(|r| r.len() - 1) else {
+        return System::Underdetermined;
+    };
+    for col in 0..unknowns {
+        let Some(pivot) = (col..rows.len()).find(|r| !rows[*r][col].is_zero()) else {
+            return System::Underdetermined;
+        };
+        rows.swap(col, pivot);
+        let pivot = rows[col][col].clone();
+        for v in rows[col].iter_mut() {
+            *v /= &pivot;
+        }
+        let pivot_row = rows[col].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r == col || row[col].is_zero() {
+                continue;
+            }
+            let factor = row[col].clone();
+            for (v, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *v -= &factor * p;
+            }
+        }
+    }
+    if rows[unknowns..].iter().any(|r| !r[unknowns].is_zero()) {
+        return System::Inconsistent; // 0 == constant
+    }
+    System::Solved(
+        rows.into_iter()
+            .take(unknowns)
+            .map(|r| r[unknowns].clone())
+            .collect(),
+    )
+}
+
+fn rock_throw(hail: &[Hailstone]) -> Option<Hailstone> {
+    // Equations from a handful of pairs are enough to pin down the 6 unknowns,
+    // using more than 2 pairs guards against a degenerate choice of pairs. If
+    // the first hailstones are still degenerate (e.g. parallel), take pairs
+    // among twice as many of them, up to all of them.
+    let mut count = 4;
+    let solution = loop {
+        let few = &hail[..hail.len().min(count)];
+        let mut rows = Vec::new();
+        for (i, a) in few.iter().enumerate() {
+            for b in &few[i + 1..] {
+                rows.extend(pair_equations(a, b));
+            }
+        }
+        match gaussian_elimination(rows) {
+            System::Solved(solution) => break solution,
+            System::Underdetermined if few.len() < hail.len() => count *= 2,
+            _ => return None,
+        }
+    };
+    let solution = solution
+        .into_iter()
+        .map(|v| v.is_integer().then(|| v.to_integer().to_i64()).flatten())
+        .collect::<Option<Vec<_>>>()?;
+    let rock = Hailstone {
+        pos: _3D {
+            x: solution[0],
+            y: solution[1],
+            z: solution[2],
+        },
+        vel: _3D {
+            x: solution[3],
+            y: solution[4],
+            z: solution[5],
+        },
+    };
+    hail.iter()
+        .all(|h| collision(&rock, h).is_some_and(|(t, _)| t >= Q::zero()))
+        .then_some(rock)
+}
+
+pub fn part_two(input: &str) -> Option<i64> {
+    let hail = parse(input);
+    let rock = rock_throw(&hail)?;
+    Some(rock.pos.x + rock.pos.y + rock.pos.z)
 }
 
 #[cfg(test)]
@@ -231,6 +319,37 @@ mod tests {
     #[test]
     fn test_part_two() {
         let result = part_two(&advent_of_code::template::read_file("examples", DAY));
-        assert_eq!(result, None);
+        assert_eq!(result, Some(47));
+    }
+
+    #[test]
+    fn test_rock_throw() {
+        let hail = parse(&advent_of_code::template::read_file("examples", DAY));
+        let rock = rock_throw(&hail).unwrap();
+        assert_eq!((rock.pos.x, rock.pos.y, rock.pos.z), (24, 13, 10));
+        assert_eq!((rock.vel.x, rock.vel.y, rock.vel.z), (-3, 1, 2));
+
+        // The first hailstones say nothing together when they are the same
+        let input = advent_of_code::template::read_file("examples", DAY);
+        let first = input.lines().next().unwrap();
+        let repeated = format!("{first}\n{first}\n{first}\n{first}\n{input}");
+        let rock = rock_throw(&parse(&repeated)).unwrap();
+        assert_eq!((rock.pos.x, rock.pos.y, rock.pos.z), (24, 13, 10));
+        assert!(rock_throw(&parse(&format!("{first}\n{first}\n"))).is_none());
+    }
+
+    #[test]
+    fn test_path_crossing() {
+        let hail = parse(&advent_of_code::template::read_file("examples", DAY));
+        let rock = rock_throw(&hail).unwrap();
+        let point = _3Dq {
+            x: Q::from(9),
+            y: Q::from(18),
+            z: Q::from(20),
+        };
+        let t = Q::from(5);
+        assert_eq!(path_crossing(&rock, &hail[0]), Some((point, t, t)));
+        assert_eq!(collision(&rock, &hail[0]), Some((t, point)));
+        assert_eq!(collision(&hail[0], &hail[1]), None);
     }
 }
@@ -0,0 +1,439 @@
+//! Networks of pulse modules: flip-flops, conjunctions and a broadcaster,
+//! wired by a list of `name -> dest, dest` lines such as:
+//!
+//! ```text
+//! broadcaster -> a
+//! %a -> inv, con
+//! &inv -> b
+//! ```
+//!
+//! The network is compiled to an indexed representation: modules are
+//! identified by their position in [`Network::modules`], and the
+//! [`Simulator`] keeps all its state in flat vectors.
+use crate::parse::{adjacency, lines, ParseError};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pulse {
+    Low,
+    High,
+}
+
+impl Display for Pulse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Pulse::Low => "low",
+            Pulse::High => "high",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Sends a low pulse to the broadcaster when pushed.
+    Button,
+    /// Sends the pulses it receives to all its destinations.
+    Broadcaster,
+    /// Ignores high pulses, flips on low pulses and sends high when on.
+    FlipFlop,
+    /// Sends low when the last pulses from all its inputs were high.
+    Conjunction,
+    /// Only receives pulses, such as `rx` or `output`.
+    Untyped,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub kind: Kind,
+    /// Destinations, and the slot this module occupies in each of their
+    /// inputs.
+    dests: Vec<(usize, usize)>,
+    inputs: Vec<usize>,
+    /// Offset of this module's inputs in the simulator's memory.
+    memory: usize,
+}
+
+impl Module {
+    pub fn dests(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dests.iter().map(|(d, _)| *d)
+    }
+
+    pub fn inputs(&self) -> &[usize] {
+        &self.inputs
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    modules: Vec<Module>,
+    ids: HashMap<String, usize>,
+    memory: usize,
+}
+
+pub const BUTTON: usize = 0;
+pub const BROADCASTER: usize = 1;
+
+impl Network {
+    /// Parses a network, reporting lines with an unknown module type or
+    /// without `->`, as well as a missing broadcaster.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut network = Self {
+            modules: Vec::new(),
+            ids: HashMap::new(),
+            memory: 0,
+        };
+        network.id_or_insert("button");
+        network.id_or_insert("broadcaster");
+        network.modules[BUTTON].kind = Kind::Button;
+        let mut edges = vec![(BUTTON, BROADCASTER)];
+        let mut has_broadcaster = false;
+        for (n, line) in lines(input) {
+            let error = |message: &str| ParseError {
+                line: n,
+                column: 1,
+                message: message.to_string(),
+            };
+            let (src, dests) = match adjacency(line) {
+                Some((src, dests)) if line.contains("->") => (src, dests),
+                _ => return Err(error("expected `name -> destinations`")),
+            };
+            let (kind, name) = match src.split_at(src.find(char::is_alphanumeric).unwrap_or(0)) {
+                ("", "broadcaster") => (Kind::Broadcaster, src),
+                ("%", name) => (Kind::FlipFlop, name),
+                ("&", name) => (Kind::Conjunction, name),
+                _ => return Err(error("expected `broadcaster`, `%name` or `&name`")),
+            };
+            has_broadcaster |= kind == Kind::Broadcaster;
+            let src = network.id_or_insert(name);
+            network.modules[src].kind = kind;
+            for dest in dests {
+                edges.push((src, network.id_or_insert(dest)));
+            }
+        }
+        if !has_broadcaster {
+            return Err(ParseError {
+                line: 1,
+                column: 1,
+                message: "no broadcaster".to_string(),
+            });
+        }
+        for (src, dest) in edges {
+            let slot = network.modules[dest].inputs.len();
+            network.modules[dest].inputs.push(src);
+            network.modules[src].dests.push((dest, slot));
+        }
+        for module in network.modules.iter_mut() {
+            module.memory = network.memory;
+            network.memory += module.inputs.len();
+        }
+        Ok(network)
+    }
+
+    fn id_or_insert(&mut self, name: &str) -> usize {
+        if let Some(id) = self.id(name) {
+            return id;
+        }
+        self.ids.insert(name.to_string(), self.modules.len());
+        self.modules.push(Module {
+            name: name.to_string(),
+            kind: Kind::Untyped,
+            dests: Vec::new(),
+            inputs: Vec::new(),
+            memory: 0,
+        });
+        self.modules.len() - 1
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    pub fn module(&self, id: usize) -> &Module {
+        &self.modules[id]
+    }
+
+    /// Formats a trace as text, one event per line such as
+    /// `1: button -low-> broadcaster`.
+    pub fn trace_to_text(&self, events: &[Event]) -> String {
+        events
+            .iter()
+            .map(|e| {
+                format!(
+                    "{}: {} -{}-> {}\n",
+                    e.press, self.modules[e.src].name, e.pulse, self.modules[e.dest].name
+                )
+            })
+            .collect()
+    }
+
+    /// Formats a trace as a JSON array of objects with `press`, `src`, `dest`
+    /// and `pulse` fields.
+    pub fn trace_to_json(&self, events: &[Event]) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let objects = events
+            .iter()
+            .map(|e| {
+                format!(
+                    "{{\"press\":{},\"src\":{},\"dest\":{},\"pulse\":\"{}\"}}",
+                    e.press,
+                    quote(&self.modules[e.src].name),
+                    quote(&self.modules[e.dest].name),
+                    e.pulse
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", objects.join(","))
+    }
+}
+
+impl Display for Network {
+    /// Prints the network back in its input format, sorted by name.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut modules = self
+            .modules
+            .iter()
+            .filter(|m| !m.dests.is_empty() && m.kind != Kind::Button)
+            .collect::<Vec<_>>();
+        modules.sort_unstable_by_key(|m| &m.name);
+        for m in modules {
+            let prefix = match m.kind {
+                Kind::FlipFlop => "%",
+                Kind::Conjunction => "&",
+                _ => "",
+            };
+            let dests = m
+                .dests()
+                .map(|d| self.modules[d].name.as_str())
+                .collect::<Vec<_>>();
+            writeln!(f, "{prefix}{} -> {}", m.name, dests.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+/// A pulse sent from `src` to `dest` during the given button press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub press: usize,
+    pub src: usize,
+    pub dest: usize,
+    pub pulse: Pulse,
+}
+
+/// The state of a single module, see [`Simulator::query`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ModuleState {
+    FlipFlop(bool),
+    /// Most recent pulse from each input, in the order of
+    /// [`Module::inputs`].
+    Conjunction(Vec<Pulse>),
+    Stateless,
+}
+
+/// The state of all modules and the pulses in flight. Button presses and
+/// pulse counters are not part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct State {
+    on: Vec<bool>,
+    memory: Vec<Pulse>,
+    highs: Vec<usize>,
+    /// (src, dest, slot of src in dest's inputs, pulse)
+    pending: VecDeque<(usize, usize, usize, Pulse)>,
+}
+
+pub struct Simulator<'a> {
+    network: &'a Network,
+    state: State,
+    presses: usize,
+    sent: [u64; 2],
+    trace: Option<Vec<Event>>,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(network: &'a Network) -> Self {
+        Self {
+            network,
+            state: State {
+                on: vec![false; network.modules.len()],
+                memory: vec![Pulse::Low; network.memory],
+                highs: vec![0; network.modules.len()],
+                pending: VecDeque::new(),
+            },
+            presses: 0,
+            sent: [0, 0],
+            trace: None,
+        }
+    }
+
+    /// Starts recording every pulse processed from now on.
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    /// Number of times the button was pushed.
+    pub fn presses(&self) -> usize {
+        self.presses
+    }
+
+    /// Number of low and high pulses processed so far.
+    pub fn pulses_sent(&self) -> (u64, u64) {
+        (self.sent[0], self.sent[1])
+    }
+
+    /// Events recorded since the trace was enabled, if it was.
+    pub fn trace(&self) -> Option<&[Event]> {
+        self.trace.as_deref()
+    }
+
+    /// Queues a low pulse from the button to the broadcaster.
+    pub fn push_button(&mut self) {
+        self.presses += 1;
+        self.state
+            .pending
+            .push_back((BUTTON, BROADCASTER, 0, Pulse::Low));
+    }
+
+    /// Processes the next pulse in flight, if any, and returns it.
+    pub fn step(&mut self) -> Option<Event> {
+        let (src, dest, slot, pulse) = self.state.pending.pop_front()?;
+        let event = Event {
+            press: self.presses,
+            src,
+            dest,
+            pulse,
+        };
+        self.sent[(pulse == Pulse::High) as usize] += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(event);
+        }
+        let module = &self.network.modules[dest];
+        let output = match module.kind {
+            Kind::Button | Kind::Untyped => None,
+            Kind::Broadcaster => Some(pulse),
+            Kind::FlipFlop if pulse == Pulse::High => None,
+            Kind::FlipFlop => {
+                self.state.on[dest] = !self.state.on[dest];
+                Some(match self.state.on[dest] {
+                    true => Pulse::High,
+                    false => Pulse::Low,
+                })
+            }
+            Kind::Conjunction => {
+                let memory = &mut self.state.memory[module.memory + slot];
+                match (*memory, pulse) {
+                    (Pulse::Low, Pulse::High) => self.state.highs[dest] += 1,
+                    (Pulse::High, Pulse::Low) => self.state.highs[dest] -= 1,
+                    _ => {}
+                }
+                *memory = pulse;
+                Some(match self.state.highs[dest] == module.inputs.len() {
+                    true => Pulse::Low,
+                    false => Pulse::High,
+                })
+            }
+        };
+        if let Some(output) = output {
+            for (next, slot) in module.dests.iter() {
+                self.state.pending.push_back((dest, *next, *slot, output));
+            }
+        }
+        Some(event)
+    }
+
+    /// Pushes the button and processes pulses until none is left in flight.
+    pub fn press(&mut self) {
+        self.push_button();
+        while self.step().is_some() {}
+    }
+
+    /// A copy of the current state, which can be restored later.
+    pub fn snapshot(&self) -> State {
+        self.state.clone()
+    }
+
+    pub fn restore(&mut self, state: State) {
+        self.state = state;
+    }
+
+    pub fn query(&self, id: usize) -> ModuleState {
+        let module = &self.network.modules[id];
+        match module.kind {
+            Kind::FlipFlop => ModuleState::FlipFlop(self.state.on[id]),
+            Kind::Conjunction => ModuleState::Conjunction(
+                self.state.memory[module.memory..module.memory + module.inputs.len()].to_vec(),
+            ),
+            _ => ModuleState::Stateless,
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str =
+        "broadcaster -> a\n%a -> inv, con\n&inv -> b\n%b -> con\n&con -> output\n";
+
+    #[test]
+    fn parse_errors() {
+        let err = Network::parse("broadcaster -> a\n\n#a -> b\n").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        assert_eq!(
+            Network::parse("%a -> b\n").unwrap_err().message,
+            "no broadcaster"
+        );
+        assert!(Network::parse("broadcaster a\n").is_err());
+    }
+
+    #[test]
+    fn press_and_trace() {
+        let network = Network::parse(EXAMPLE).unwrap();
+        assert_eq!(network.to_string().lines().next(), Some("%a -> inv, con"));
+        let mut sim = Simulator::new(&network).with_trace();
+        sim.press();
+        let text = network.trace_to_text(sim.trace().unwrap());
+        assert_eq!(text.lines().next(), Some("1: button -low-> broadcaster"));
+        assert_eq!(text.lines().last(), Some("1: con -low-> output"));
+        assert_eq!(sim.pulses_sent(), (4, 4));
+        let json = network.trace_to_json(&sim.trace().unwrap()[..1]);
+        assert_eq!(
+            json,
+            r#"[{"press":1,"src":"button","dest":"broadcaster","pulse":"low"}]"#
+        );
+    }
+
+    #[test]
+    fn step_snapshot_restore() {
+        let network = Network::parse(EXAMPLE).unwrap();
+        let a = network.id("a").unwrap();
+        let con = network.id("con").unwrap();
+        let mut sim = Simulator::new(&network);
+        let initial = sim.snapshot();
+        sim.push_button();
+        assert_eq!(sim.step().map(|e| e.dest), Some(BROADCASTER));
+        assert_eq!(sim.step().map(|e| e.dest), Some(a));
+        assert_eq!(sim.query(a), ModuleState::FlipFlop(true));
+        while sim.step().is_some() {}
+        assert_eq!(
+            sim.query(con),
+            ModuleState::Conjunction(vec![Pulse::High, Pulse::High])
+        );
+        sim.restore(initial.clone());
+        assert_eq!(sim.query(a), ModuleState::FlipFlop(false));
+        for _ in 0..4 {
+            sim.press();
+        }
+        assert_eq!(sim.snapshot(), initial);
+    }
+}
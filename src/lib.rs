@@ -1,4 +1,11 @@
+pub mod circuit;
 mod day;
+pub mod memo;
+pub mod nonogram;
+pub mod parse;
+pub mod polygon;
+pub mod progression;
+pub mod sequence;
 pub mod template;
 
 pub use day::*;
@@ -0,0 +1,99 @@
+//! Memoization keyed on indices, such as positions in borrowed slices.
+//!
+//! Instead of hashing owned copies of the arguments of a recursive function,
+//! the arguments are reduced to indices (e.g. an offset in a string and an
+//! offset in a list) and results are stored in a flat table. Lookups do not
+//! allocate, and the table can be reset and reused between inputs.
+
+/// A table of memoized values of type `T`, indexed by `N` indices whose
+/// bounds are given by `dims`.
+///
+/// ```
+/// # use advent_of_code::memo::Memo;
+/// fn binomial(n: usize, k: usize, memo: &mut Memo<u64, 2>) -> u64 {
+///     if k == 0 || k == n {
+///         return 1;
+///     }
+///     memo.memoize([n, k], |memo| {
+///         binomial(n - 1, k - 1, memo) + binomial(n - 1, k, memo)
+///     })
+/// }
+/// let mut memo = Memo::new([61, 61]);
+/// assert_eq!(binomial(60, 30, &mut memo), 118264581564861424);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Memo<T, const N: usize> {
+    dims: [usize; N],
+    cells: Vec<Option<T>>,
+}
+
+impl<T: Clone, const N: usize> Memo<T, N> {
+    pub fn new(dims: [usize; N]) -> Self {
+        let mut memo = Self {
+            dims,
+            cells: Vec::new(),
+        };
+        memo.reset(dims);
+        memo
+    }
+
+    /// Forgets all values and changes the bounds of the table, reusing its
+    /// allocation when possible.
+    pub fn reset(&mut self, dims: [usize; N]) {
+        self.dims = dims;
+        self.cells.clear();
+        self.cells.resize(dims.iter().product(), None);
+    }
+
+    fn offset(&self, key: [usize; N]) -> usize {
+        key.iter().zip(self.dims.iter()).fold(0, |acc, (k, d)| {
+            assert!(k < d, "key {key:?} out of bounds {:?}", self.dims);
+            acc * d + k
+        })
+    }
+
+    pub fn get(&self, key: [usize; N]) -> Option<&T> {
+        self.cells[self.offset(key)].as_ref()
+    }
+
+    pub fn insert(&mut self, key: [usize; N], value: T) {
+        let offset = self.offset(key);
+        self.cells[offset] = Some(value);
+    }
+
+    /// Returns the value for `key`, computing it with `f` if it is not known
+    /// yet. `f` receives the table so that it can recurse.
+    pub fn memoize(&mut self, key: [usize; N], f: impl FnOnce(&mut Self) -> T) -> T {
+        if let Some(value) = self.get(key) {
+            return value.clone();
+        }
+        let value = f(self);
+        self.insert(key, value.clone());
+        value
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::Memo;
+
+    #[test]
+    fn memoize_and_reset() {
+        let mut memo = Memo::new([2, 3]);
+        assert_eq!(memo.memoize([1, 2], |_| 5), 5);
+        assert_eq!(memo.memoize([1, 2], |_| 6), 5);
+        assert_eq!(memo.get([0, 2]), None);
+        memo.reset([4, 4]);
+        assert_eq!(memo.get([1, 2]), None);
+        memo.insert([3, 3], 7);
+        assert_eq!(memo.get([3, 3]), Some(&7));
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_bounds() {
+        Memo::<u8, 2>::new([2, 3]).get([0, 3]);
+    }
+}
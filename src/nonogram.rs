@@ -0,0 +1,400 @@
+//! Nonograms: lines of cells to fill so that runs of filled cells match a
+//! list of clues.
+//!
+//! A line is written with `#` for filled cells, `.` for empty cells and `?`
+//! for unknown ones; its clues are the lengths of its runs of filled cells,
+//! in order. The same logic counts the arrangements of damaged springs in
+//! day 12 (`#` damaged, `.` operational), and solves 2D puzzles where both
+//! rows and columns have clues, read from a clue file:
+//!
+//! ```text
+//! # A smiley
+//! rows
+//! 1 1
+//! -
+//! 3
+//! columns
+//! 1, 1
+//! 1
+//! 1 1
+//! ```
+//!
+//! Clues of a line are separated by spaces or commas, and an empty line is
+//! written `0` or `-`. `cargo run --example nonogram <file>` prints the
+//! solution of a clue file.
+//!
+//! ```
+//! # use advent_of_code::nonogram::Nonogram;
+//! let puzzle = Nonogram::parse("rows\n1 1\n-\n3\ncolumns\n1, 1\n1\n1 1").unwrap();
+//! let grid = puzzle.solve().unwrap();
+//! assert_eq!(grid.to_string(), "#.#\n...\n###\n");
+//! ```
+use crate::memo::Memo;
+use crate::parse::{lines, Cursor, ParseError, ParseResult};
+use std::fmt::Display;
+
+pub const FILLED: u8 = b'#';
+pub const EMPTY: u8 = b'.';
+pub const UNKNOWN: u8 = b'?';
+
+/// A line of cells and its clues.
+pub struct Line<'a> {
+    cells: &'a [u8],
+    clues: &'a [usize],
+    // empty[i] counts EMPTY in cells[..i], so a run of filled cells fits at
+    // cells[i..i + len] if empty[i + len] == empty[i].
+    empty: Vec<usize>,
+}
+
+impl<'a> Line<'a> {
+    pub fn new(cells: &'a [u8], clues: &'a [usize]) -> Self {
+        let mut empty = vec![0; cells.len() + 1];
+        for (i, c) in cells.iter().enumerate() {
+            empty[i + 1] = empty[i] + usize::from(*c == EMPTY);
+        }
+        Self {
+            cells,
+            clues,
+            empty,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    fn fits(&self, i: usize, len: usize) -> bool {
+        i + len <= self.cells.len()
+            && self.empty[i + len] == self.empty[i]
+            && self.cells.get(i + len) != Some(&FILLED)
+    }
+
+    fn reset(&self, memo: &mut Memo<usize, 2>) {
+        memo.reset([self.cells.len(), self.clues.len() + 1]);
+    }
+
+    /// Counts arrangements of cells[i..] that match clues[j..], saturating at
+    /// `usize::MAX`.
+    fn count_from(&self, i: usize, j: usize, memo: &mut Memo<usize, 2>) -> usize {
+        if i >= self.cells.len() {
+            return usize::from(j == self.clues.len());
+        }
+        memo.memoize([i, j], |memo| {
+            let mut count = 0;
+            if self.cells[i] != FILLED {
+                // Empty cell here
+                count = self.count_from(i + 1, j, memo);
+            }
+            if self.cells[i] != EMPTY && j < self.clues.len() && self.fits(i, self.clues[j]) {
+                // Next run starts here, skip the empty cell after it
+                let rest = self.count_from(i + self.clues[j] + 1, j + 1, memo);
+                count = count.saturating_add(rest);
+            }
+            count
+        })
+    }
+
+    /// Number of ways to fill unknown cells so that the line matches its
+    /// clues, saturating at `usize::MAX`. `memo` is reset, so that it can be
+    /// reused from one line to the next.
+    pub fn count(&self, memo: &mut Memo<usize, 2>) -> usize {
+        self.reset(memo);
+        self.count_from(0, 0, memo)
+    }
+
+    /// All the ways to fill unknown cells so that the line matches its clues.
+    pub fn arrangements(&self, memo: &mut Memo<usize, 2>) -> Vec<Vec<u8>> {
+        self.reset(memo);
+        let mut arrangements = vec![];
+        let mut cells = vec![EMPTY; self.cells.len()];
+        self.enumerate(0, 0, &mut cells, memo, &mut arrangements);
+        arrangements
+    }
+
+    fn enumerate(
+        &self,
+        i: usize,
+        j: usize,
+        cells: &mut Vec<u8>,
+        memo: &mut Memo<usize, 2>,
+        arrangements: &mut Vec<Vec<u8>>,
+    ) {
+        if self.count_from(i, j, memo) == 0 {
+            return; // Dead end
+        }
+        if i >= self.cells.len() {
+            arrangements.push(cells.clone());
+            return;
+        }
+        if self.cells[i] != FILLED {
+            self.enumerate(i + 1, j, cells, memo, arrangements);
+        }
+        if self.cells[i] != EMPTY && j < self.clues.len() && self.fits(i, self.clues[j]) {
+            let end = i + self.clues[j];
+            cells[i..end].fill(FILLED);
+            self.enumerate(end + 1, j + 1, cells, memo, arrangements);
+            cells[i..end].fill(EMPTY);
+        }
+    }
+
+    /// The line with the cells that are the same in all arrangements filled
+    /// in, and the others unknown, or `None` if there is no arrangement.
+    pub fn forced(&self, memo: &mut Memo<usize, 2>) -> Option<Vec<u8>> {
+        // Arrangements of cells[i..] matching clues[j..] are counted by
+        // count_from(i, j); those of cells[..i] matching clues[..j] are
+        // counted the same way on the reversed line. A cell can be empty if
+        // arrangements on both sides of it match clues split somewhere, and
+        // filled if some run of filled cells can be placed over it, with
+        // arrangements on both sides of the run.
+        if self.count(memo) == 0 {
+            return None;
+        }
+        let (n, m) = (self.cells.len(), self.clues.len());
+        let rev_cells = self.cells.iter().rev().copied().collect::<Vec<_>>();
+        let rev_clues = self.clues.iter().rev().copied().collect::<Vec<_>>();
+        let rev = Line::new(&rev_cells, &rev_clues);
+        let mut rev_memo = Memo::new([0, 0]);
+        rev.reset(&mut rev_memo);
+        let mut before = |i: usize, j: usize| rev.count_from(n - i, m - j, &mut rev_memo) > 0;
+
+        let mut can_fill = vec![false; n];
+        let mut can_empty = vec![false; n];
+        for i in 0..n {
+            if self.cells[i] != FILLED {
+                can_empty[i] = (0..=m).any(|j| before(i, j) && self.count_from(i + 1, j, memo) > 0);
+            }
+            for (j, len) in self.clues.iter().enumerate() {
+                let starts = i == 0 || self.cells[i - 1] != FILLED;
+                if self.cells[i] == EMPTY || !starts || !self.fits(i, *len) {
+                    continue;
+                }
+                let before = if i == 0 { j == 0 } else { before(i - 1, j) };
+                if before && self.count_from(i + len + 1, j + 1, memo) > 0 {
+                    can_fill[i..i + len].fill(true);
+                }
+            }
+        }
+        let forced = can_fill.iter().zip(can_empty.iter()).map(|fe| match fe {
+            (true, false) => FILLED,
+            (false, true) => EMPTY,
+            _ => UNKNOWN,
+        });
+        Some(forced.collect())
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+/// A grid of cells, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: usize,
+    cells: Vec<u8>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            cells: vec![UNKNOWN; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.len() / self.width.max(1)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.cells[y * self.width + x]
+    }
+
+    pub fn row(&self, y: usize) -> &[u8] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn column(&self, x: usize) -> Vec<u8> {
+        (0..self.height()).map(|y| self.get(x, y)).collect()
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height() {
+            writeln!(f, "{}", String::from_utf8_lossy(self.row(y)))?;
+        }
+        Ok(())
+    }
+}
+
+/// A 2D puzzle, with clues for each row (top to bottom) and each column
+/// (left to right).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nonogram {
+    pub rows: Vec<Vec<usize>>,
+    pub columns: Vec<Vec<usize>>,
+}
+
+impl Nonogram {
+    /// Reads a clue file, see the [module documentation](self).
+    pub fn parse(input: &str) -> ParseResult<Self> {
+        let (mut rows, mut columns) = (vec![], vec![]);
+        let mut section: Option<&mut Vec<Vec<usize>>> = None;
+        for (n, line) in lines(input) {
+            let mut c = Cursor::at_line(line, n);
+            c.space();
+            if c.rest().starts_with('#') {
+                continue;
+            }
+            if c.rest().starts_with(char::is_alphabetic) {
+                section = match c.one_of(&["rows", "columns"])? {
+                    0 => Some(&mut rows),
+                    _ => Some(&mut columns),
+                };
+                c.end()?;
+                continue;
+            }
+            let Some(clues) = section.as_mut() else {
+                return Err(c.error("expected \"rows\" or \"columns\" first"));
+            };
+            let mut line = vec![];
+            if !c.opt_tag("-") {
+                while !c.rest().is_empty() {
+                    line.push(c.unsigned()?);
+                    c.space();
+                    c.opt_tag(",");
+                    c.space();
+                }
+            }
+            c.end()?;
+            line.retain(|n| *n > 0);
+            clues.push(line);
+        }
+        let error = |message: &str| ParseError {
+            line: input.lines().count(),
+            column: 1,
+            message: message.to_string(),
+        };
+        if rows.is_empty() || columns.is_empty() {
+            return Err(error("rows and columns need clues"));
+        }
+        Ok(Self { rows, columns })
+    }
+
+    /// A grid matching all clues, if there is one. When there are several,
+    /// the first one found is returned.
+    pub fn solve(&self) -> Option<Grid> {
+        let mut memo = Memo::new([0, 0]);
+        self.search(Grid::new(self.columns.len(), self.rows.len()), &mut memo)
+    }
+
+    fn search(&self, mut grid: Grid, memo: &mut Memo<usize, 2>) -> Option<Grid> {
+        // Fill in forced cells line by line, until there is nothing left to
+        // learn; only lines crossing updated cells need another look. Then
+        // guess the first unknown cell, and backtrack if that goes wrong.
+        let (width, height) = (grid.width(), grid.height());
+        let mut dirty_rows = vec![true; height];
+        let mut dirty_columns = vec![true; width];
+        while dirty_rows.contains(&true) || dirty_columns.contains(&true) {
+            while let Some(y) = dirty_rows.iter().position(|d| *d) {
+                dirty_rows[y] = false;
+                let forced = Line::new(grid.row(y), &self.rows[y]).forced(memo)?;
+                for (x, cell) in forced.into_iter().enumerate() {
+                    if grid.get(x, y) != cell {
+                        grid.cells[y * width + x] = cell;
+                        dirty_columns[x] = true;
+                    }
+                }
+            }
+            while let Some(x) = dirty_columns.iter().position(|d| *d) {
+                dirty_columns[x] = false;
+                let column = grid.column(x);
+                let forced = Line::new(&column, &self.columns[x]).forced(memo)?;
+                for (y, cell) in forced.into_iter().enumerate() {
+                    if grid.get(x, y) != cell {
+                        grid.cells[y * width + x] = cell;
+                        dirty_rows[y] = true;
+                    }
+                }
+            }
+        }
+        let Some(unknown) = grid.cells.iter().position(|c| *c == UNKNOWN) else {
+            return Some(grid);
+        };
+        [FILLED, EMPTY].into_iter().find_map(|guess| {
+            let mut grid = grid.clone();
+            grid.cells[unknown] = guess;
+            self.search(grid, memo)
+        })
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line() {
+        let mut memo = Memo::new([0, 0]);
+        let line = Line::new(b"???.###", &[1, 1, 3]);
+        assert_eq!(line.count(&mut memo), 1);
+        assert_eq!(line.arrangements(&mut memo), vec![b"#.#.###".to_vec()]);
+        let line = Line::new(b"?###????????", &[3, 2, 1]);
+        assert_eq!(line.count(&mut memo), 10);
+        assert_eq!(line.arrangements(&mut memo).len(), 10);
+        assert_eq!(line.forced(&mut memo), Some(b".###.???????".to_vec()));
+        let line = Line::new(b"??????????", &[8]);
+        assert_eq!(line.forced(&mut memo), Some(b"??######??".to_vec()));
+        assert_eq!(Line::new(b"#.#", &[3]).forced(&mut memo), None);
+    }
+
+    #[test]
+    fn module_example() {
+        // Doc tests are disabled, so check the clue file from the module docs
+        let docs = include_str!("nonogram.rs");
+        let clues = docs
+            .lines()
+            .skip_while(|l| *l != "//! ```text")
+            .skip(1)
+            .take_while(|l| *l != "//! ```")
+            .map(|l| l.trim_start_matches("//!").trim())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let grid = Nonogram::parse(&clues).unwrap().solve().unwrap();
+        assert_eq!(grid.to_string(), "#.#\n...\n###\n");
+    }
+
+    #[test]
+    fn solve() {
+        // Needs a guess: the puzzle has two solutions
+        let puzzle = Nonogram::parse("rows\n1\n1\ncolumns\n1\n1").unwrap();
+        let grid = puzzle.solve().unwrap();
+        assert_eq!(grid.to_string(), "#.\n.#\n");
+
+        let puzzle = Nonogram::parse("rows\n1\n3\n1 1\ncolumns\n2\n2, 0\n-").unwrap();
+        assert_eq!(puzzle.columns, vec![vec![2], vec![2], vec![]]);
+
+        let puzzle =
+            Nonogram::parse("# A heart\nrows\n1 1\n5\n5\n3\n1\n\ncolumns\n2\n4\n4\n4\n2\n")
+                .unwrap();
+        let grid = puzzle.solve().unwrap();
+        assert_eq!(grid.to_string(), ".#.#.\n#####\n#####\n.###.\n..#..\n");
+        assert_eq!(grid.column(2), b".####".to_vec());
+
+        assert!(Nonogram::parse("rows\n2\ncolumns\n1\n-")
+            .unwrap()
+            .solve()
+            .is_none());
+        let error = Nonogram::parse("rows\n1 x\ncolumns\n1").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+    }
+}
@@ -0,0 +1,417 @@
+//! Helpers shared by solutions to read puzzle inputs.
+//!
+//! Regular expressions used here are compiled once, on first use, and cached
+//! for the lifetime of the program. Solutions should do the same with
+//! [`regex!`](crate::regex) rather than calling `Regex::new` in their solvers,
+//! otherwise the compilation is repeated on every benchmark iteration.
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+pub use regex::Regex;
+
+/// Returns a `&'static Regex` for the given pattern, compiled on first use.
+/// Each call site caches a single regex, so its pattern must not change
+/// between calls: it panics if it does, in release builds too.
+///
+/// ```
+/// let re = advent_of_code::regex!(r"(\w+) = \((\w+), (\w+)\)");
+/// assert!(re.is_match("AAA = (BBB, CCC)"));
+/// ```
+#[macro_export]
+macro_rules! regex {
+    ($re:expr $(,)?) => {{
+        let pattern: &str = $re;
+        {
+            // Scoped so that the static cannot shadow a name used in `$re`.
+            static RE: ::std::sync::OnceLock<$crate::parse::Regex> = ::std::sync::OnceLock::new();
+            let re = RE.get_or_init(|| $crate::parse::Regex::new(pattern).unwrap());
+            assert_eq!(
+                re.as_str(),
+                pattern,
+                "regex! call site reused with another pattern"
+            );
+            re
+        }
+    }};
+}
+
+/* -------------------------------------------------------------------------- */
+
+fn parse_match<T>(m: regex::Match) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    m.as_str()
+        .parse()
+        .unwrap_or_else(|e| panic!("cannot parse {:?}: {:?}", m.as_str(), e))
+}
+
+/// Iterates over all unsigned integers found in `s`, ignoring any other
+/// character (a leading `-` is dropped).
+///
+/// # Panics
+/// When a number does not fit in `T`.
+pub fn unsigned<'a, T>(s: &'a str) -> impl Iterator<Item = T> + 'a
+where
+    T: FromStr + 'a,
+    T::Err: Debug,
+{
+    crate::regex!(r"\d+").find_iter(s).map(parse_match)
+}
+
+/// Iterates over all signed integers found in `s`, ignoring any other
+/// character.
+///
+/// # Panics
+/// When a number does not fit in `T`.
+pub fn signed<'a, T>(s: &'a str) -> impl Iterator<Item = T> + 'a
+where
+    T: FromStr + 'a,
+    T::Err: Debug,
+{
+    crate::regex!(r"-?\d+").find_iter(s).map(parse_match)
+}
+
+/// Iterates over the paragraphs of `s`, i.e. blocks of text separated by one
+/// or more blank lines. Paragraphs are trimmed and never empty.
+pub fn paragraphs(s: &str) -> impl Iterator<Item = &str> {
+    crate::regex!(r"\n[ \t\r]*\n")
+        .split(s)
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+}
+
+/// Iterates over the non-blank lines of `s` with their line number, starting
+/// at 1. Lines are trimmed of trailing whitespace.
+pub fn lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .map(str::trim_end)
+        .enumerate()
+        .filter(|(_, l)| !l.trim_start().is_empty())
+        .map(|(i, l)| (i + 1, l))
+}
+
+/// Iterates over `key=value` or `key: value` pairs found in `s`, such as
+/// `{x=787,m=2655}`. Keys are words, values extend until the next separator.
+pub fn key_values(s: &str) -> impl Iterator<Item = (&str, &str)> {
+    crate::regex!(r"(\w+)\s*[=:]\s*([^\s,;{}]+)")
+        .captures_iter(s)
+        .map(|c| {
+            let (_, [k, v]) = c.extract();
+            (k, v)
+        })
+}
+
+/// Parses an adjacency line such as `a -> b, c` or `a: b c` into its source
+/// and list of destinations. The source is returned as is, so prefixes like
+/// `%` or `&` are left for the caller to interpret.
+///
+/// Returns [`None`] if the line has no `->` or `:` separator.
+pub fn adjacency(line: &str) -> Option<(&str, Vec<&str>)> {
+    let (src, dst) = line.split_once("->").or_else(|| line.split_once(':'))?;
+    Some((
+        src.trim(),
+        dst.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .collect(),
+    ))
+}
+
+/* -------------------------------------------------------------------------- */
+
+/// An error returned by a [`Cursor`], located by line and column (both
+/// starting at 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// A position in an input, with small combinators to consume it.
+///
+/// Every combinator either consumes what it recognized and returns it, or
+/// leaves the cursor untouched and returns a [`ParseError`] pointing at the
+/// current position.
+///
+/// ```
+/// # use advent_of_code::parse::Cursor;
+/// let mut c = Cursor::new("Card 1: 41 48 | 83 86");
+/// c.tag("Card").unwrap();
+/// c.space();
+/// assert_eq!(c.unsigned::<u32>(), Ok(1));
+/// assert!(c.tag(":").is_ok());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Cursor<'a> {
+    input: &'a str,
+    pos: usize,
+    first_line: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self::at_line(input, 1)
+    }
+
+    /// Creates a cursor over `input`, reporting errors as if `input` started
+    /// at line `line` of a larger document.
+    pub fn at_line(input: &'a str, line: usize) -> Self {
+        Self {
+            input,
+            pos: 0,
+            first_line: line,
+        }
+    }
+
+    /// The input left to consume.
+    pub fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    /// Builds an error located at the current position.
+    pub fn error(&self, message: impl Into<String>) -> ParseError {
+        let before = &self.input[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            line: self.first_line + before.matches('\n').count(),
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+
+    fn expected<T>(&self, what: &str) -> ParseResult<T> {
+        let found = match self.rest().chars().next() {
+            Some(c) => format!("{c:?}"),
+            None => "end of input".to_string(),
+        };
+        Err(self.error(format!("expected {what}, found {found}")))
+    }
+
+    /// Consumes and returns the longest prefix whose characters match `pred`.
+    pub fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Skips spaces and tabs.
+    pub fn space(&mut self) {
+        self.take_while(|c| c == ' ' || c == '\t');
+    }
+
+    /// Consumes `tag`, or fails.
+    pub fn tag(&mut self, tag: &str) -> ParseResult<()> {
+        if self.rest().starts_with(tag) {
+            self.pos += tag.len();
+            Ok(())
+        } else {
+            self.expected(&format!("{tag:?}"))
+        }
+    }
+
+    /// Consumes `tag` if present, and returns whether it was.
+    pub fn opt_tag(&mut self, tag: &str) -> bool {
+        self.tag(tag).is_ok()
+    }
+
+    /// Consumes a non-empty word made of alphanumeric characters or `_`.
+    pub fn word(&mut self) -> ParseResult<&'a str> {
+        match self.take_while(|c| c.is_alphanumeric() || c == '_') {
+            "" => self.expected("a word"),
+            word => Ok(word),
+        }
+    }
+
+    /// Consumes the first of `choices` that is a prefix of the input and
+    /// returns its index in `choices`.
+    pub fn one_of(&mut self, choices: &[&str]) -> ParseResult<usize> {
+        match choices.iter().position(|c| self.rest().starts_with(c)) {
+            Some(i) => {
+                self.pos += choices[i].len();
+                Ok(i)
+            }
+            None => self.expected(&format!("one of {choices:?}")),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self, digits: &str, what: &str) -> ParseResult<T> {
+        match digits.parse() {
+            Ok(n) => {
+                self.pos += digits.len();
+                Ok(n)
+            }
+            Err(_) if digits.is_empty() => self.expected(what),
+            Err(_) => Err(self.error(format!("{digits} is out of range"))),
+        }
+    }
+
+    /// Consumes an unsigned integer.
+    pub fn unsigned<T: FromStr>(&mut self) -> ParseResult<T> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        self.number(&rest[..len], "an unsigned integer")
+    }
+
+    /// Consumes an integer, optionally preceded by `-`.
+    pub fn signed<T: FromStr>(&mut self) -> ParseResult<T> {
+        let rest = self.rest();
+        let sign = usize::from(rest.starts_with('-'));
+        let len = rest[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - sign);
+        match len {
+            0 => self.expected("an integer"),
+            _ => self.number(&rest[..sign + len], "an integer"),
+        }
+    }
+
+    /// Consumes a match of `re`, which must start at the current position.
+    pub fn pattern(&mut self, re: &Regex) -> ParseResult<&'a str> {
+        match re.find(self.rest()) {
+            Some(m) if m.start() == 0 => {
+                self.pos += m.end();
+                Ok(m.as_str())
+            }
+            _ => self.expected(&format!("/{}/", re.as_str())),
+        }
+    }
+
+    /// Parses one or more items with `item`, separated by `sep`. Spaces
+    /// around separators are skipped.
+    pub fn sep_by<T>(
+        &mut self,
+        sep: &str,
+        mut item: impl FnMut(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<Vec<T>> {
+        let mut items = vec![item(self)?];
+        loop {
+            let save = *self;
+            self.space();
+            if !self.opt_tag(sep) {
+                *self = save;
+                return Ok(items);
+            }
+            self.space();
+            items.push(item(self)?);
+        }
+    }
+
+    /// Succeeds only if all the input was consumed, ignoring trailing
+    /// whitespace.
+    pub fn end(&mut self) -> ParseResult<()> {
+        let save = *self;
+        self.take_while(char::is_whitespace);
+        if self.is_empty() {
+            Ok(())
+        } else {
+            *self = save;
+            self.expected("end of line")
+        }
+    }
+}
+
+/// Parses each non-blank line of `input` with `parser`, which must consume
+/// the entire line. Errors report the line and column in `input`.
+pub fn parse_lines<'a, T>(
+    input: &'a str,
+    mut parser: impl FnMut(&mut Cursor<'a>) -> ParseResult<T>,
+) -> ParseResult<Vec<T>> {
+    lines(input)
+        .map(|(n, line)| {
+            let mut cursor = Cursor::at_line(line, n);
+            let value = parser(&mut cursor)?;
+            cursor.end()?;
+            Ok(value)
+        })
+        .collect()
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "another pattern")]
+    fn regex_call_site() {
+        let cached = |pattern: &str| crate::regex!(pattern).as_str().to_string();
+        assert_eq!(cached("a+"), "a+");
+        cached("b+");
+    }
+
+    #[test]
+    fn integers() {
+        let s = "x=-12, y=4 -> 7";
+        assert_eq!(unsigned::<u32>(s).collect::<Vec<_>>(), vec![12, 4, 7]);
+        assert_eq!(signed::<i32>(s).collect::<Vec<_>>(), vec![-12, 4, 7]);
+    }
+
+    #[test]
+    fn splitters() {
+        let s = "seeds: 1 2\n\na:\n1 2 3\n  \nb:\n4 5 6\n";
+        assert_eq!(
+            paragraphs(s).collect::<Vec<_>>(),
+            vec!["seeds: 1 2", "a:\n1 2 3", "b:\n4 5 6"]
+        );
+        assert_eq!(
+            lines(s).map(|(n, _)| n).collect::<Vec<_>>(),
+            vec![1, 3, 4, 6, 7]
+        );
+    }
+
+    #[test]
+    fn key_values_and_adjacency() {
+        assert_eq!(
+            key_values("{x=787,m=2655}").collect::<Vec<_>>(),
+            vec![("x", "787"), ("m", "2655")]
+        );
+        assert_eq!(adjacency("%a -> b, c"), Some(("%a", vec!["b", "c"])));
+        assert_eq!(adjacency("jqt: rhn xhk"), Some(("jqt", vec!["rhn", "xhk"])));
+        assert_eq!(adjacency("nothing here"), None);
+    }
+
+    #[test]
+    fn cursor_errors() {
+        let input = "1, 2, 3\n4, x, 6\n";
+        let err = parse_lines(input, |c| c.sep_by(",", Cursor::unsigned::<u32>)).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 4: expected an unsigned integer, found 'x'"
+        );
+
+        let mut c = Cursor::new("-5 red");
+        assert_eq!(c.signed::<i8>(), Ok(-5));
+        c.space();
+        assert_eq!(c.one_of(&["green", "red"]), Ok(1));
+        assert!(c.end().is_ok());
+
+        let err = Cursor::new("300").unsigned::<u8>().unwrap_err();
+        assert_eq!(err.message, "300 is out of range");
+    }
+}
@@ -0,0 +1,174 @@
+//! Simple polygons on the integer lattice.
+//!
+//! Areas are computed with the shoelace formula and lattice point counts with
+//! Pick's theorem, so everything stays exact: for a polygon of area `A` with
+//! `B` lattice points on its boundary, the number of lattice points strictly
+//! inside is `I = A - B / 2 + 1`.
+use num::integer::gcd;
+
+/// A point on the lattice, as `(x, y)`.
+pub type Point = (i64, i64);
+
+/// A simple (non self-intersecting) polygon whose vertices are lattice points.
+/// The last vertex is implicitly connected back to the first one.
+///
+/// Lattice point counts assume the polygon is simple with a non-zero area. In
+/// particular, no edge may walk back over the previous one: the boundary of a
+/// segment, or of a spike such as `R 2, L 1`, would be counted twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+impl Polygon {
+    /// Creates a polygon from its vertices, in order. Repeated consecutive
+    /// vertices are dropped, as well as a last vertex equal to the first one.
+    pub fn from_vertices(vertices: impl IntoIterator<Item = Point>) -> Self {
+        let mut vertices = vertices.into_iter().collect::<Vec<_>>();
+        vertices.dedup();
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        Self { vertices }
+    }
+
+    /// Creates a polygon by walking from `start`, following a list of
+    /// `(direction, length)` instructions, where `direction` is a step such
+    /// as `(1, 0)` or `(0, -1)`. Consecutive moves along the same direction
+    /// are merged into a single edge. If the walk does not end on `start`, it
+    /// is closed by a straight edge.
+    ///
+    /// ```
+    /// # use advent_of_code::polygon::Polygon;
+    /// let square = Polygon::from_instructions(
+    ///     (0, 0),
+    ///     [((1, 0), 2), ((0, 1), 2), ((-1, 0), 2), ((0, -1), 2)],
+    /// );
+    /// assert_eq!(square.lattice_points(), 9);
+    /// ```
+    pub fn from_instructions(
+        start: Point,
+        instructions: impl IntoIterator<Item = (Point, i64)>,
+    ) -> Self {
+        let mut vertices = vec![start];
+        let mut last_dir = None;
+        let mut head = start;
+        for ((dx, dy), len) in instructions {
+            head = (head.0 + dx * len, head.1 + dy * len);
+            if last_dir == Some((dx, dy)) {
+                *vertices.last_mut().unwrap() = head;
+            } else {
+                vertices.push(head);
+            }
+            last_dir = Some((dx, dy));
+        }
+        Self::from_vertices(vertices)
+    }
+
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
+    }
+
+    /// Whether all edges are horizontal or vertical.
+    pub fn is_rectilinear(&self) -> bool {
+        self.edges().all(|(a, b)| a.0 == b.0 || a.1 == b.1)
+    }
+
+    /// Twice the signed area, which is always an integer. It is positive when
+    /// vertices go counter-clockwise with the y axis pointing up (clockwise
+    /// with the y axis pointing down, as in most puzzle grids).
+    pub fn double_signed_area(&self) -> i64 {
+        self.edges().map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum()
+    }
+
+    /// The area, rounded down if it is not an integer (it is always a multiple
+    /// of 1/2).
+    pub fn area(&self) -> i64 {
+        self.double_signed_area().abs() / 2
+    }
+
+    /// Number of lattice points on the edges of the polygon.
+    pub fn boundary_points(&self) -> i64 {
+        self.edges().map(|(a, b)| gcd(b.0 - a.0, b.1 - a.1)).sum()
+    }
+
+    /// Number of lattice points strictly inside the polygon (Pick's theorem).
+    /// Degenerate polygons, for which the theorem does not hold, have none.
+    pub fn interior_points(&self) -> i64 {
+        ((self.double_signed_area().abs() - self.boundary_points() + 2) / 2).max(0)
+    }
+
+    /// Number of lattice points on the boundary or inside the polygon. When
+    /// each lattice point stands for a grid tile, this is the number of tiles
+    /// covered by the polygon, including its outline.
+    pub fn lattice_points(&self) -> i64 {
+        self.interior_points() + self.boundary_points()
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectilinear() {
+        // An L shape, walked clockwise with y pointing down
+        let l = Polygon::from_instructions(
+            (0, 0),
+            [
+                ((1, 0), 1),
+                ((1, 0), 1),
+                ((0, 1), 1),
+                ((-1, 0), 1),
+                ((0, 1), 1),
+                ((-1, 0), 1),
+                ((0, -1), 2),
+            ],
+        );
+        assert_eq!(
+            l.vertices(),
+            &[(0, 0), (2, 0), (2, 1), (1, 1), (1, 2), (0, 2)]
+        );
+        assert!(l.is_rectilinear());
+        assert_eq!(l.double_signed_area(), 6);
+        assert_eq!(l.area(), 3);
+        assert_eq!(l.boundary_points(), 8);
+        assert_eq!(l.interior_points(), 0);
+        assert_eq!(l.lattice_points(), 8);
+    }
+
+    #[test]
+    fn general() {
+        let t = Polygon::from_vertices([(0, 0), (0, 4), (4, 0), (0, 0)]);
+        assert_eq!(t.vertices().len(), 3);
+        assert!(!t.is_rectilinear());
+        assert_eq!(t.double_signed_area(), -16);
+        assert_eq!(t.area(), 8);
+        assert_eq!(t.boundary_points(), 12);
+        assert_eq!(t.interior_points(), 3);
+    }
+
+    #[test]
+    fn degenerate() {
+        let segment = Polygon::from_vertices([(0, 0), (3, 0)]);
+        assert_eq!(segment.area(), 0);
+        assert_eq!(segment.boundary_points(), 6);
+        assert_eq!(segment.interior_points(), 0);
+
+        let spike = Polygon::from_instructions(
+            (0, 0),
+            [((1, 0), 2), ((-1, 0), 1), ((0, 1), 1), ((-1, 0), 1)],
+        );
+        assert_eq!(spike.area(), 1);
+        assert_eq!(spike.interior_points(), 0);
+    }
+}
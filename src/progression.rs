@@ -0,0 +1,77 @@
+//! Arithmetic progressions of times, such as the steps or presses at which a
+//! cycling system hits some state, and their intersection.
+//!
+//! Two progressions `a1 + k * m1` and `a2 + k * m2` meet when
+//! `a1 = a2 (mod gcd(m1, m2))`, and then form a progression with the lcm of
+//! both periods (generalized CRT). Times before a cycle is entered are
+//! progressions with a period of 0.
+use num::integer::{gcd, lcm, ExtendedGcd};
+use num::Integer;
+
+/// Times `first + k * period` for all k >= 0, or only `first` if `period`
+/// is 0.
+pub type Progression = (u64, u64);
+
+/// Times that are in both progressions, if any.
+pub fn intersect((a1, m1): Progression, (a2, m2): Progression) -> Option<Progression> {
+    match (m1, m2) {
+        (0, 0) => (a1 == a2).then_some((a1, 0)),
+        (0, m) => (a1 >= a2 && (a1 - a2) % m == 0).then_some((a1, 0)),
+        (m, 0) => (a2 >= a1 && (a2 - a1) % m == 0).then_some((a2, 0)),
+        _ => {
+            // Generalized CRT: x = a1 + m1 * k and m1 * k = a2 - a1 (mod m2)
+            let (a1, m1, a2, m2) = (a1 as i128, m1 as i128, a2 as i128, m2 as i128);
+            let g = gcd(m1, m2);
+            if (a2 - a1) % g != 0 {
+                return None;
+            }
+            let ExtendedGcd { x: inv, .. } = (m1 / g).extended_gcd(&(m2 / g));
+            let k = ((a2 - a1) / g * inv).rem_euclid(m2 / g);
+            let modulus = lcm(m1, m2);
+            let mut x = (a1 + m1 * k).rem_euclid(modulus);
+            // Smallest solution that both progressions reach
+            let floor = a1.max(a2);
+            if x < floor {
+                x += (floor - x + modulus - 1) / modulus * modulus;
+            }
+            Some((x as u64, modulus as u64))
+        }
+    }
+}
+
+/// Times within `from` that are in one progression of each set. Each set
+/// describes one system, e.g. the times it hits any of several states.
+pub fn intersect_all(
+    from: Progression,
+    sets: impl IntoIterator<Item = Vec<Progression>>,
+) -> Vec<Progression> {
+    let mut common = vec![from];
+    for set in sets {
+        common = common
+            .iter()
+            .flat_map(|a| set.iter().filter_map(|b| intersect(*a, *b)))
+            .collect();
+    }
+    common
+}
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs() {
+        assert_eq!(intersect((3, 4), (5, 6)), Some((11, 12)));
+        assert_eq!(intersect((3, 4), (4, 6)), None);
+        assert_eq!(intersect((15, 4), (5, 6)), Some((23, 12)));
+        assert_eq!(intersect((7, 0), (1, 3)), Some((7, 0)));
+        assert_eq!(intersect((7, 0), (8, 1)), None);
+    }
+
+    #[test]
+    fn sets() {
+        let common = intersect_all((1, 1), [vec![(2, 2)], vec![(1, 3), (3, 3)]]);
+        assert_eq!(common, [(4, 6), (6, 6)]);
+        assert!(intersect_all((0, 1), [vec![(2, 2)], vec![(1, 2)]]).is_empty());
+    }
+}
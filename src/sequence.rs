@@ -0,0 +1,111 @@
+//! Integer sequences given by a polynomial of the index.
+//!
+//! A sequence of `m` values is fitted exactly by the polynomial of degree less
+//! than `m` in Newton's binomial form: with `d_k` the first value of the k-th
+//! row of differences, the value at index `n` is the sum of `d_k * C(n, k)`.
+//! Binomials of any integer `n`, even negative, are integers, so values can be
+//! extrapolated both ways without rational arithmetic.
+
+/// The polynomial of minimal degree through a sequence of values, indexed from
+/// 0 for the first value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    /// First value of each row of differences, up to the last non-zero one
+    differences: Vec<i128>,
+    len: usize,
+    exact: bool,
+}
+
+impl Polynomial {
+    /// Fits `values` exactly.
+    pub fn fit(values: &[i64]) -> Self {
+        let mut row = values.iter().map(|v| *v as i128).collect::<Vec<_>>();
+        let mut differences = Vec::with_capacity(row.len());
+        while let Some(first) = row.first() {
+            differences.push(*first);
+            row = row.windows(2).map(|w| w[1] - w[0]).collect();
+        }
+        // A row of zeros is reached if the last row, of a single value, is 0
+        let exact = differences.last() == Some(&0);
+        while differences.last() == Some(&0) {
+            differences.pop();
+        }
+        Self {
+            differences,
+            len: values.len(),
+            exact,
+        }
+    }
+
+    /// Whether the differences reached a row of zeros within the sequence.
+    /// Otherwise, any value could follow and extrapolation is a guess.
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// Degree of the polynomial, with 0 for the zero polynomial.
+    pub fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    /// Value at index `n`.
+    pub fn at(&self, n: i64) -> i64 {
+        // C(n, k + 1) = C(n, k) * (n - k) / (k + 1), where the division is
+        // exact since both binomials are integers.
+        let mut binomial = 1_i128;
+        let mut value = 0;
+        for (k, d) in self.differences.iter().enumerate() {
+            value += d * binomial;
+            binomial = binomial * (n as i128 - k as i128) / (k as i128 + 1);
+        }
+        value as i64
+    }
+
+    /// Value `steps` after the last one of the sequence.
+    pub fn forward(&self, steps: usize) -> i64 {
+        self.at(self.len as i64 - 1 + steps as i64)
+    }
+
+    /// Value `steps` before the first one of the sequence.
+    pub fn backward(&self, steps: usize) -> i64 {
+        self.at(-(steps as i64))
+    }
+}
+
+#[cfg(feature = "test_lib")]
+mod tests {
+    use super::Polynomial;
+
+    #[test]
+    fn fit() {
+        let squares = [1, 4, 9, 16, 25];
+        let p = Polynomial::fit(&squares);
+        assert!(p.is_exact());
+        assert_eq!(p.degree(), 2);
+        assert_eq!((0..5).map(|n| p.at(n)).collect::<Vec<_>>(), squares);
+        assert_eq!(p.forward(1), 36);
+        assert_eq!(p.forward(5), 100);
+        assert_eq!(p.backward(1), 0);
+        assert_eq!(p.backward(3), 4);
+
+        let cubes = [-8, -1, 0, 1, 8];
+        let p = Polynomial::fit(&cubes);
+        assert_eq!((p.degree(), p.is_exact()), (3, true));
+        assert_eq!((p.forward(1), p.backward(1)), (27, -27));
+
+        let constant = Polynomial::fit(&[7, 7]);
+        assert_eq!((constant.degree(), constant.forward(10)), (0, 7));
+        let zero = Polynomial::fit(&[0, 0, 0]);
+        assert_eq!((zero.degree(), zero.backward(2)), (0, 0));
+    }
+
+    #[test]
+    fn inexact() {
+        // Differences 1, 2, 4: the last row of a single value is not zero
+        let p = Polynomial::fit(&[1, 2, 4, 8]);
+        assert!(!p.is_exact());
+        assert_eq!(p.degree(), 3);
+        assert_eq!(p.forward(1), 15);
+        assert!(!Polynomial::fit(&[]).is_exact());
+    }
+}